@@ -0,0 +1,157 @@
+use crate::lib::colour;
+use clap::{Parser, ValueEnum};
+use std::path;
+
+/// Path tracer based on Ray Tracing in One Weekend.
+///
+/// Options left unset keep the values from the selected scene.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Options {
+  /// Built-in scene name (random-spheres), or path to a TOML or glTF (.gltf/.glb) scene file
+  #[arg(short, long, default_value = "random-spheres")]
+  pub scene: String,
+
+  /// Equirectangular .hdr or .exr image to light the scene with, replacing its environment
+  #[arg(long)]
+  pub environment: Option<path::PathBuf>,
+
+  /// Rotation of the --environment image about the vertical axis, in degrees
+  #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+  pub environment_rotation: f64,
+
+  /// Multiplier for the radiance of the --environment image
+  #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+  pub environment_intensity: f64,
+
+  /// Where to write the rendered image, as PNG, OpenEXR (.exr) or Radiance HDR (.hdr)
+  #[arg(short, long, default_value = "img.png")]
+  pub output: path::PathBuf,
+
+  /// Include an alpha channel of geometry coverage (.exr output only)
+  #[arg(long)]
+  pub alpha: bool,
+
+  /// Exposure adjustment in stops applied before tone mapping (.png output only)
+  #[arg(short, long, default_value_t = 0.0, allow_negative_numbers = true)]
+  pub exposure: f64,
+
+  /// Tone mapping operator (.png output only)
+  #[arg(short, long, value_enum, default_value_t = ToneMapName::None)]
+  pub tone_map: ToneMapName,
+
+  /// Radiance mapped to white by reinhard-extended
+  #[arg(long, default_value_t = 4.0, value_parser = parse_positive)]
+  pub white_point: f64,
+
+  /// Encoding of .png output, recorded in the file so viewers decode it correctly
+  #[arg(long, value_enum, default_value_t = TransferName::Srgb)]
+  pub transfer: TransferName,
+
+  /// Exponent used by the gamma transfer function
+  #[arg(long, default_value_t = 2.2, value_parser = parse_positive)]
+  pub gamma: f64,
+
+  /// Image width in pixels
+  #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+  pub width: Option<u32>,
+
+  /// Image aspect ratio, as a number (1.5) or width:height (16:9)
+  #[arg(short, long, value_parser = parse_aspect_ratio)]
+  pub aspect_ratio: Option<f64>,
+
+  /// Samples per pixel
+  #[arg(short = 'n', long, value_parser = clap::value_parser!(u32).range(1..))]
+  pub samples: Option<u32>,
+
+  /// Maximum number of ray bounces
+  #[arg(short = 'd', long, value_parser = clap::value_parser!(u32).range(1..))]
+  pub max_depth: Option<u32>,
+
+  /// Bounces before Russian roulette starts ending paths that carry little light
+  #[arg(long)]
+  pub roulette_depth: Option<u32>,
+
+  /// Number of render threads [default: number of CPUs]
+  #[arg(short = 'j', long, value_parser = clap::value_parser!(u32).range(1..))]
+  pub threads: Option<u32>,
+
+  /// Seed for the random scene layout and pixel sampling, to reproduce an image exactly
+  #[arg(long)]
+  pub seed: Option<u64>,
+
+  /// Don't show the progress bar
+  #[arg(short, long)]
+  pub quiet: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ToneMapName {
+  None,
+  Reinhard,
+  ReinhardExtended,
+  Aces,
+  Hable,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum TransferName {
+  Srgb,
+  Gamma,
+  Linear,
+}
+
+impl Options {
+  pub fn display_transform(&self) -> colour::DisplayTransform {
+    let tone_map = match self.tone_map {
+      ToneMapName::None => colour::ToneMap::None,
+      ToneMapName::Reinhard => colour::ToneMap::Reinhard,
+      ToneMapName::ReinhardExtended => colour::ToneMap::ReinhardExtended {
+        white_point: self.white_point,
+      },
+      ToneMapName::Aces => colour::ToneMap::Aces,
+      ToneMapName::Hable => colour::ToneMap::Hable,
+    };
+    let transfer = match self.transfer {
+      TransferName::Srgb => colour::TransferFunction::Srgb,
+      TransferName::Gamma => colour::TransferFunction::Gamma(self.gamma),
+      TransferName::Linear => colour::TransferFunction::Linear,
+    };
+    colour::DisplayTransform {
+      exposure: self.exposure,
+      tone_map,
+      transfer,
+    }
+  }
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+  match s.parse::<f64>() {
+    Ok(x) if x.is_finite() && x > 0.0 => Ok(x),
+    Ok(_) => Err("must be greater than 0".to_string()),
+    Err(_) => Err(format!("`{s}` is not a number")),
+  }
+}
+
+fn parse_aspect_ratio(s: &str) -> Result<f64, String> {
+  let ratio = match s.split_once(':') {
+    Some((w, h)) => {
+      let w: f64 = w
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid width `{w}`"))?;
+      let h: f64 = h
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid height `{h}`"))?;
+      w / h
+    }
+    None => s.parse().map_err(|_| format!("`{s}` is not a number"))?,
+  };
+
+  if ratio.is_finite() && ratio > 0.0 {
+    Ok(ratio)
+  } else {
+    Err("aspect ratio must be greater than 0".to_string())
+  }
+}
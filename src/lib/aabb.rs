@@ -0,0 +1,124 @@
+use crate::lib::interval::{self, Interval};
+use crate::lib::{ray, vector};
+
+// Axis-aligned bounding box, stored as one interval per axis
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Aabb {
+  pub x: Interval,
+  pub y: Interval,
+  pub z: Interval,
+}
+
+impl Aabb {
+  pub fn new(x: Interval, y: Interval, z: Interval) -> Aabb {
+    let mut bbox = Aabb { x, y, z };
+    bbox.pad_to_minimums();
+    bbox
+  }
+
+  // Box with a and b as opposite corners, in any order
+  pub fn from_points(a: vector::Vector, b: vector::Vector) -> Aabb {
+    Aabb::new(
+      Interval::new(a.x.min(b.x), a.x.max(b.x)),
+      Interval::new(a.y.min(b.y), a.y.max(b.y)),
+      Interval::new(a.z.min(b.z), a.z.max(b.z)),
+    )
+  }
+
+  // Smallest box containing both a and b
+  pub fn enclosing(a: &Aabb, b: &Aabb) -> Aabb {
+    Aabb {
+      x: Interval::enclosing(&a.x, &b.x),
+      y: Interval::enclosing(&a.y, &b.y),
+      z: Interval::enclosing(&a.z, &b.z),
+    }
+  }
+
+  pub fn axis_interval(&self, n: usize) -> &Interval {
+    match n {
+      0 => &self.x,
+      1 => &self.y,
+      _ => &self.z,
+    }
+  }
+
+  pub fn longest_axis(&self) -> usize {
+    if self.x.size() > self.y.size() {
+      if self.x.size() > self.z.size() {
+        0
+      } else {
+        2
+      }
+    } else if self.y.size() > self.z.size() {
+      1
+    } else {
+      2
+    }
+  }
+
+  pub fn centroid(&self) -> vector::Vector {
+    vector::Vector::new(
+      (self.x.min + self.x.max) / 2.0,
+      (self.y.min + self.y.max) / 2.0,
+      (self.z.min + self.z.max) / 2.0,
+    )
+  }
+
+  pub fn surface_area(&self) -> f64 {
+    let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+    2.0 * ((dx * dy) + (dy * dz) + (dz * dx))
+  }
+
+  // True for boxes that extend forever along some axis, such as a plane's
+  pub fn is_unbounded(&self) -> bool {
+    (0..3).any(|n| self.axis_interval(n).size() == f64::INFINITY)
+  }
+
+  // Slab test: true if the ray passes through the box anywhere within ray_t
+  pub fn hit(&self, r: &ray::Ray, mut ray_t: Interval) -> bool {
+    // The slabs of an empty box are infinite, which the test below would count as a hit
+    if (0..3).any(|n| self.axis_interval(n).is_empty()) {
+      return false;
+    }
+    for axis in 0..3 {
+      let ax = self.axis_interval(axis);
+      let adinv = 1.0 / r.dir.axis(axis);
+
+      let t0 = (ax.min - r.orig.axis(axis)) * adinv;
+      let t1 = (ax.max - r.orig.axis(axis)) * adinv;
+
+      ray_t.min = ray_t.min.max(t0.min(t1));
+      ray_t.max = ray_t.max.min(t0.max(t1));
+
+      if ray_t.max <= ray_t.min {
+        return false;
+      }
+    }
+    true
+  }
+
+  fn pad_to_minimums(&mut self) {
+    // Flat boxes (e.g. around a planar primitive) would be missed by the slab test
+    let delta = 0.0001;
+    if self.x.size() < delta {
+      self.x = self.x.expand(delta);
+    }
+    if self.y.size() < delta {
+      self.y = self.y.expand(delta);
+    }
+    if self.z.size() < delta {
+      self.z = self.z.expand(delta);
+    }
+  }
+}
+
+pub const EMPTY: Aabb = Aabb {
+  x: interval::EMPTY,
+  y: interval::EMPTY,
+  z: interval::EMPTY,
+};
+pub const UNIVERSE: Aabb = Aabb {
+  x: interval::UNIVERSE,
+  y: interval::UNIVERSE,
+  z: interval::UNIVERSE,
+};
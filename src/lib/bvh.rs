@@ -0,0 +1,286 @@
+use crate::lib::{aabb, hittable, hittable_list, interval, ray};
+use std::sync;
+
+// Number of centroid buckets evaluated by the surface area heuristic
+const SAH_BUCKETS: usize = 12;
+
+pub struct BvhNode {
+  left: sync::Arc<dyn hittable::Hittable>,
+  right: sync::Arc<dyn hittable::Hittable>,
+  bbox: aabb::Aabb,
+}
+
+impl BvhNode {
+  pub fn new(list: hittable_list::HittableList) -> BvhNode {
+    BvhNode::from_objects(list.objects)
+  }
+
+  pub fn from_objects(objects: Vec<sync::Arc<dyn hittable::Hittable>>) -> BvhNode {
+    // Unbounded objects such as planes have no position to sort by, so they sit in a list
+    // beside the tree of everything else
+    let (unbounded, mut objects): (Vec<_>, Vec<_>) = objects
+      .into_iter()
+      .partition(|object| object.bounding_box().is_unbounded());
+    if !unbounded.is_empty() && !objects.is_empty() {
+      let mut list = hittable_list::HittableList::new_empty();
+      for object in unbounded {
+        list.add(object);
+      }
+      let left: sync::Arc<dyn hittable::Hittable> = sync::Arc::new(list);
+      let right = BvhNode::child(objects);
+      return BvhNode {
+        bbox: aabb::Aabb::enclosing(&left.bounding_box(), &right.bounding_box()),
+        left,
+        right,
+      };
+    }
+    if objects.is_empty() {
+      objects = unbounded;
+    }
+
+    match objects.len() {
+      0 => {
+        let empty: sync::Arc<dyn hittable::Hittable> =
+          sync::Arc::new(hittable_list::HittableList::new_empty());
+        BvhNode {
+          left: empty.clone(),
+          right: empty,
+          bbox: aabb::EMPTY,
+        }
+      }
+      1 => {
+        let object = objects.remove(0);
+        BvhNode {
+          bbox: object.bounding_box(),
+          left: object.clone(),
+          right: object,
+        }
+      }
+      2 => {
+        let right = objects.remove(1);
+        let left = objects.remove(0);
+        BvhNode {
+          bbox: aabb::Aabb::enclosing(&left.bounding_box(), &right.bounding_box()),
+          left,
+          right,
+        }
+      }
+      _ => {
+        let right_objects = BvhNode::split(&mut objects);
+        let left = BvhNode::child(objects);
+        let right = BvhNode::child(right_objects);
+        BvhNode {
+          bbox: aabb::Aabb::enclosing(&left.bounding_box(), &right.bounding_box()),
+          left,
+          right,
+        }
+      }
+    }
+  }
+
+  fn child(
+    mut objects: Vec<sync::Arc<dyn hittable::Hittable>>,
+  ) -> sync::Arc<dyn hittable::Hittable> {
+    if objects.len() == 1 {
+      objects.remove(0)
+    } else {
+      sync::Arc::new(BvhNode::from_objects(objects))
+    }
+  }
+
+  // Partitions objects using the surface area heuristic over bucketed centroids along the
+  // longest centroid axis. Objects is left holding the first half; the second half is returned.
+  fn split(
+    objects: &mut Vec<sync::Arc<dyn hittable::Hittable>>,
+  ) -> Vec<sync::Arc<dyn hittable::Hittable>> {
+    let centroid_bounds = objects.iter().fold(aabb::EMPTY, |bounds, object| {
+      let c = object.bounding_box().centroid();
+      aabb::Aabb::enclosing(&bounds, &aabb::Aabb::from_points(c, c))
+    });
+    let axis = centroid_bounds.longest_axis();
+    let axis_bounds = *centroid_bounds.axis_interval(axis);
+
+    let centroid =
+      |object: &sync::Arc<dyn hittable::Hittable>| object.bounding_box().centroid().axis(axis);
+    objects.sort_by(|a, b| centroid(a).total_cmp(&centroid(b)));
+
+    let bucket = |object: &sync::Arc<dyn hittable::Hittable>| {
+      let offset = (centroid(object) - axis_bounds.min) / axis_bounds.size();
+      ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+    };
+
+    // Cost of splitting after each bucket: area-weighted object counts on either side
+    let mut best_split = objects.len() / 2;
+    let mut best_cost = f64::INFINITY;
+    for split_bucket in 0..SAH_BUCKETS - 1 {
+      let split = objects.partition_point(|object| bucket(object) <= split_bucket);
+      if split == 0 || split == objects.len() {
+        continue;
+      }
+      let cost = BvhNode::area(&objects[..split]) * split as f64
+        + BvhNode::area(&objects[split..]) * (objects.len() - split) as f64;
+      if cost < best_cost {
+        best_cost = cost;
+        best_split = split;
+      }
+    }
+
+    objects.split_off(best_split)
+  }
+
+  fn area(objects: &[sync::Arc<dyn hittable::Hittable>]) -> f64 {
+    objects
+      .iter()
+      .fold(aabb::EMPTY, |bounds, object| {
+        aabb::Aabb::enclosing(&bounds, &object.bounding_box())
+      })
+      .surface_area()
+  }
+}
+
+impl hittable::Hittable for BvhNode {
+  fn hit(
+    &self,
+    r: &ray::Ray,
+    ray_t: interval::Interval,
+    hit_rec: &mut hittable::HitRecord,
+  ) -> bool {
+    if !self.bbox.hit(r, ray_t) {
+      return false;
+    }
+
+    let hit_left = self.left.hit(r, ray_t, hit_rec);
+    let right_max = if hit_left { hit_rec.t } else { ray_t.max };
+    let hit_right = self
+      .right
+      .hit(r, interval::Interval::new(ray_t.min, right_max), hit_rec);
+
+    hit_left || hit_right
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lib::vector::Vector;
+  use crate::lib::{material, plane, quad, sphere, triangle, utility};
+
+  fn scene() -> Vec<sync::Arc<dyn hittable::Hittable>> {
+    let mat: sync::Arc<dyn material::Material> =
+      sync::Arc::new(material::Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+    let mut objects: Vec<sync::Arc<dyn hittable::Hittable>> =
+      vec![sync::Arc::new(plane::Plane::new(
+        Vector::new(0.0, -5.0, 0.0),
+        Vector::new(0.0, 1.0, 0.0),
+        mat.clone(),
+      ))];
+    let point = || Vector::random(-4.0, 4.0);
+    for _ in 0..20 {
+      objects.push(sync::Arc::new(sphere::Sphere::new(
+        point(),
+        utility::random(0.1, 1.0),
+        mat.clone(),
+      )));
+      let corner = point();
+      objects.push(sync::Arc::new(triangle::Triangle::new(
+        corner,
+        corner + Vector::random(-1.0, 1.0),
+        corner + Vector::random(-1.0, 1.0),
+        mat.clone(),
+      )));
+      objects.push(sync::Arc::new(quad::Quad::new(
+        point(),
+        Vector::random(-1.0, 1.0),
+        Vector::random(-1.0, 1.0),
+        mat.clone(),
+      )));
+      let corner = point();
+      objects.push(sync::Arc::new(quad::Cuboid::new(
+        corner,
+        corner + Vector::random(0.1, 1.0),
+        mat.clone(),
+      )));
+    }
+    objects
+  }
+
+  fn random_ray() -> ray::Ray {
+    ray::Ray::new(Vector::random(-8.0, 8.0), Vector::random_unit_vector())
+  }
+
+  fn hit(world: &dyn hittable::Hittable, r: &ray::Ray) -> Option<hittable::HitRecord> {
+    let mut rec = hittable::HitRecord::new_empty();
+    world
+      .hit(
+        r,
+        interval::Interval::new(0.001, utility::INFINITY),
+        &mut rec,
+      )
+      .then_some(rec)
+  }
+
+  #[test]
+  fn matches_flat_list() {
+    utility::seed(2);
+    let objects = scene();
+    let bvh = BvhNode::from_objects(objects.clone());
+    let mut list = hittable_list::HittableList::new_empty();
+    for object in objects {
+      list.add(object);
+    }
+
+    let mut hits = 0;
+    for _ in 0..5000 {
+      let r = random_ray();
+      match (hit(&bvh, &r), hit(&list, &r)) {
+        (None, None) => {}
+        (Some(a), Some(b)) => {
+          assert_eq!(a.t, b.t);
+          assert_eq!(a.normal, b.normal);
+          hits += 1;
+        }
+        (a, b) => panic!(
+          "bvh hit {} but list hit {} for {:?}",
+          a.is_some(),
+          b.is_some(),
+          r.dir
+        ),
+      }
+    }
+    // Most rays should hit something, or the comparison proves little
+    assert!(hits > 2500, "only {hits} rays hit");
+  }
+
+  #[test]
+  fn finds_objects_in_nested_lists() {
+    let mat: sync::Arc<dyn material::Material> =
+      sync::Arc::new(material::Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+    let mut nested = hittable_list::HittableList::new_empty();
+    nested.add(sync::Arc::new(sphere::Sphere::new(
+      Vector::new(0.0, 0.0, 0.0),
+      1.0,
+      mat.clone(),
+    )));
+    let other = sync::Arc::new(sphere::Sphere::new(Vector::new(10.0, 0.0, 0.0), 1.0, mat));
+    let bvh = BvhNode::from_objects(vec![sync::Arc::new(nested), other]);
+
+    let r = ray::Ray::new(Vector::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, -1.0));
+    let rec = hit(&bvh, &r).expect("ray aimed at the nested sphere missed");
+    assert!((rec.t - 4.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn empty_box_is_never_hit() {
+    utility::seed(4);
+    let empty = BvhNode::from_objects(Vec::new());
+    for _ in 0..100 {
+      let r = random_ray();
+      assert!(!aabb::EMPTY.hit(&r, interval::UNIVERSE));
+      assert!(hit(&empty, &r).is_none());
+    }
+  }
+}
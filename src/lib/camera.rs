@@ -1,5 +1,25 @@
-use crate::lib::{colour, hittable, interval, ray, utility, vector};
-use image::{ImageBuffer, Rgb, RgbImage};
+use crate::lib::hittable::Hittable;
+use crate::lib::{
+  colour, environment, framebuffer, hittable, hittable_list, interval, progress, ray, utility,
+  vector,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use std::{path, thread};
+
+// Width and height in pixels of the square tiles handed out to render threads.
+const TILE_SIZE: usize = 16;
+
+// Bounces every path gets before Russian roulette may end it
+pub const DEFAULT_ROULETTE_DEPTH: u32 = 5;
+
+struct Tile {
+  index: usize,
+  x: usize,
+  y: usize,
+  width: usize,
+  height: usize,
+}
 
 pub struct Camera {
   // aspect ratio and plane size
@@ -14,6 +34,7 @@ pub struct Camera {
   pixel_delta_v: vector::Vector,
   pixel_samples_scale: f64,
   max_depth: u32,
+  roulette_depth: u32,
   lookfrom: vector::Vector,
   lookat: vector::Vector,
   vup: vector::Vector,
@@ -21,9 +42,17 @@ pub struct Camera {
 
   defocus_angle: f64,
   focus_dist: f64,
+  shutter_open: f64,
+  shutter_close: f64,
 
   defocus_disk_u: vector::Vector,
   defocus_disk_v: vector::Vector,
+
+  threads: usize,
+  progress: Box<dyn progress::Progress>,
+  seed: Option<u64>,
+  environment: Box<dyn environment::Environment>,
+  lights: hittable_list::HittableList,
 }
 
 impl Default for Camera {
@@ -40,14 +69,22 @@ impl Default for Camera {
       pixel_delta_v: vector::Vector::new(0.0, 0.0, 0.0),
       pixel_samples_scale: 0.0,
       max_depth: 50,
+      roulette_depth: DEFAULT_ROULETTE_DEPTH,
       lookfrom: vector::Vector::new(0.0, 0.0, 0.0),
       lookat: vector::Vector::new(0.0, 0.0, -1.0),
       vup: vector::Vector::new(0.0, 1.0, 0.0),
       vfov: 90.0,
       defocus_angle: 0.0,
       focus_dist: 10.0,
+      shutter_open: 0.0,
+      shutter_close: 1.0,
       defocus_disk_u: vector::Vector::new(0.0, 0.0, 0.0),
       defocus_disk_v: vector::Vector::new(0.0, 0.0, 0.0),
+      threads: Camera::available_threads(),
+      progress: Box::new(progress::TerminalBar::new()),
+      seed: None,
+      environment: Box::new(environment::Gradient::sky()),
+      lights: hittable_list::HittableList::new_empty(),
     };
 
     // Initialize computed fields
@@ -59,6 +96,7 @@ impl Default for Camera {
 
 impl Camera {
   // Constructor that initializes the camera with image width and aspect ratio.
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     aspect_ratio: f64,
     image_width: usize,
@@ -82,14 +120,22 @@ impl Camera {
       pixel_delta_v: vector::Vector::new(0.0, 0.0, 0.0),
       pixel_samples_scale: 0.0,
       max_depth,
+      roulette_depth: DEFAULT_ROULETTE_DEPTH,
       lookfrom,
       lookat,
       vup,
       vfov,
       defocus_angle,
       focus_dist,
+      shutter_open: 0.0,
+      shutter_close: 1.0,
       defocus_disk_u: vector::Vector::new(0.0, 0.0, 0.0),
       defocus_disk_v: vector::Vector::new(0.0, 0.0, 0.0),
+      threads: Camera::available_threads(),
+      progress: Box::new(progress::TerminalBar::new()),
+      seed: None,
+      environment: Box::new(environment::Gradient::sky()),
+      lights: hittable_list::HittableList::new_empty(),
     };
 
     // Initialize computed fields
@@ -98,6 +144,86 @@ impl Camera {
     camera
   }
 
+  // Sets the number of worker threads used by render, at least one.
+  pub fn set_threads(&mut self, threads: usize) {
+    self.threads = threads.max(1);
+  }
+
+  // Replaces the default terminal progress bar, e.g. with progress::Silent
+  pub fn set_progress(&mut self, progress: Box<dyn progress::Progress>) {
+    self.progress = progress;
+  }
+
+  // With a seed, every render of the same scene produces a bit-identical image regardless of
+  // the number of threads. Without one, each render draws fresh random samples.
+  pub fn set_seed(&mut self, seed: Option<u64>) {
+    self.seed = seed;
+  }
+
+  // What rays that miss everything see, the sky gradient by default
+  pub fn set_environment(&mut self, environment: Box<dyn environment::Environment>) {
+    self.environment = environment;
+  }
+
+  // Emissive objects to sample directly wherever a path scatters off a non-delta lobe. They must
+  // also be in the world being rendered; only their pdf_value and random are used from here.
+  pub fn set_lights(&mut self, lights: hittable_list::HittableList) {
+    self.lights = lights;
+  }
+
+  pub fn set_image_width(&mut self, image_width: usize) {
+    self.image_width = image_width;
+    self.initialize();
+  }
+
+  pub fn set_aspect_ratio(&mut self, aspect_ratio: f64) {
+    self.aspect_ratio = aspect_ratio;
+    self.initialize();
+  }
+
+  pub fn set_samples_per_pixel(&mut self, samples_per_pixel: u32) {
+    self.samples_per_pixel = samples_per_pixel;
+    self.initialize();
+  }
+
+  // Hard limit on the number of bounces. Russian roulette usually ends paths long before it.
+  pub fn set_max_depth(&mut self, max_depth: u32) {
+    self.max_depth = max_depth;
+  }
+
+  // After this many bounces, paths are ended at random with a probability that grows as the
+  // light they can still carry shrinks. Survivors are brightened to make up for the ones
+  // ended, so the image stays unbiased.
+  pub fn set_roulette_depth(&mut self, roulette_depth: u32) {
+    self.roulette_depth = roulette_depth;
+  }
+
+  // Moves the camera to lookfrom, facing lookat, with a vertical field of view in degrees
+  pub fn set_view(
+    &mut self,
+    lookfrom: vector::Vector,
+    lookat: vector::Vector,
+    vup: vector::Vector,
+    vfov: f64,
+  ) {
+    self.lookfrom = lookfrom;
+    self.lookat = lookat;
+    self.vup = vup;
+    self.vfov = vfov;
+    self.initialize();
+  }
+
+  // Each ray is taken at a random time between open and close, blurring anything that moves in
+  // between. Open and close may be equal for a frozen moment; by default they're 0 and 1.
+  pub fn set_shutter(&mut self, open: f64, close: f64) {
+    self.shutter_open = open;
+    self.shutter_close = close.max(open);
+  }
+
+  fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+  }
+
   fn initialize(&mut self) {
     self.image_height = (self.image_width as f64 / self.aspect_ratio) as usize;
     self.image_height = self.image_height.max(1);
@@ -135,30 +261,104 @@ impl Camera {
 }
 
 impl Camera {
-  pub fn render(&self, world: &dyn hittable::Hittable) {
-    let mut count = 1;
-    let max = self.image_width * self.image_height;
-
-    let mut buffer: RgbImage = ImageBuffer::new(self.image_width as u32, self.image_height as u32);
-    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
-      println!("count: {count} max: {max}");
+  pub fn render(&self, world: &dyn hittable::Hittable) -> framebuffer::Framebuffer {
+    let tiles = self.tiles();
+    let total = self.image_width * self.image_height;
+    let next_tile = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let start = Instant::now();
+
+    // Each worker pulls the next unrendered tile until none are left
+    let rendered: Vec<(&Tile, Vec<(vector::Vector, f64)>)> = thread::scope(|s| {
+      let workers: Vec<_> = (0..self.threads.min(tiles.len()))
+        .map(|_| {
+          s.spawn(|| {
+            let mut done = Vec::new();
+            while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+              done.push((tile, self.render_tile(tile, world)));
+
+              let pixels = tile.width * tile.height;
+              let count = completed.fetch_add(pixels, Ordering::Relaxed) + pixels;
+              self.progress.update(count, total, start.elapsed());
+            }
+            done
+          })
+        })
+        .collect();
+
+      workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap())
+        .collect()
+    });
+
+    let mut image = framebuffer::Framebuffer::new(self.image_width, self.image_height);
+    for (tile, pixels) in rendered {
+      for (i, (colour, alpha)) in pixels.into_iter().enumerate() {
+        let (x, y) = (tile.x + i % tile.width, tile.y + i / tile.width);
+        image.set(x, y, colour);
+        image.set_alpha(x, y, alpha);
+      }
+    }
 
-      let mut pixel_colour = vector::Vector::new(0.0, 0.0, 0.0);
+    self.progress.finish(total, start.elapsed());
 
-      for _ in 0..=self.samples_per_pixel {
-        let r = self.get_ray(x, y);
-        pixel_colour = pixel_colour + Camera::ray_colour(&r, self.max_depth, world);
-      }
+    image
+  }
 
-      let (r, g, b) = colour::get_colour(&(pixel_colour * self.pixel_samples_scale));
-      *pixel = Rgb([r, g, b]);
+  // Renders the world and writes it straight to a PNG file, ignoring the path's extension
+  #[allow(dead_code)]
+  pub fn render_png(
+    &self,
+    world: &dyn hittable::Hittable,
+    path: impl AsRef<path::Path>,
+  ) -> Result<(), framebuffer::SaveError> {
+    self
+      .render(world)
+      .save_png(path, &colour::DisplayTransform::default())
+  }
 
-      count += 1;
+  fn tiles(&self) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..self.image_height).step_by(TILE_SIZE) {
+      for x in (0..self.image_width).step_by(TILE_SIZE) {
+        tiles.push(Tile {
+          index: tiles.len(),
+          x,
+          y,
+          width: TILE_SIZE.min(self.image_width - x),
+          height: TILE_SIZE.min(self.image_height - y),
+        });
+      }
     }
+    tiles
+  }
 
-    buffer.save("img.png").unwrap();
+  // Renders the colour and coverage of each pixel of a tile in row-major order
+  fn render_tile(&self, tile: &Tile, world: &dyn hittable::Hittable) -> Vec<(vector::Vector, f64)> {
+    if let Some(seed) = self.seed {
+      // Tiles may run on any thread in any order, so each gets its own random stream
+      utility::seed(seed ^ (tile.index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    }
 
-    println!("DONE!");
+    let mut pixels = Vec::with_capacity(tile.width * tile.height);
+    for y in tile.y..tile.y + tile.height {
+      for x in tile.x..tile.x + tile.width {
+        let mut pixel_colour = vector::Vector::new(0.0, 0.0, 0.0);
+        let mut hits = 0;
+
+        for _ in 0..self.samples_per_pixel {
+          let r = self.get_ray(x as u32, y as u32);
+          let (colour, hit) = self.trace(&r, world);
+          pixel_colour = pixel_colour + colour;
+          hits += hit as u32;
+        }
+
+        let alpha = hits as f64 * self.pixel_samples_scale;
+        pixels.push((pixel_colour * self.pixel_samples_scale, alpha));
+      }
+    }
+    pixels
   }
 
   pub fn get_ray(&self, i: u32, j: u32) -> ray::Ray {
@@ -173,8 +373,13 @@ impl Camera {
       self.defocus_disk_sample()
     };
     let ray_direction = pixel_sample - ray_origin;
+    let ray_time = if self.shutter_close > self.shutter_open {
+      utility::random(self.shutter_open, self.shutter_close)
+    } else {
+      self.shutter_open
+    };
 
-    ray::Ray::new(ray_origin, ray_direction)
+    ray::Ray::with_time(ray_origin, ray_direction, ray_time)
   }
 
   pub fn defocus_disk_sample(&self) -> vector::Vector {
@@ -186,33 +391,302 @@ impl Camera {
     vector::Vector::new(utility::random_df() - 0.5, utility::random_df() - 0.5, 0.0)
   }
 
-  pub fn ray_colour(r: &ray::Ray, depth: u32, world: &dyn hittable::Hittable) -> vector::Vector {
-    if depth <= 0 {
-      return vector::Vector::new(0.0, 0.0, 0.0);
+  // Colour of the light arriving back along r, and whether r itself hit anything in the world.
+  //
+  // Follows the path bounce by bounce, keeping the product of the attenuations so far in
+  // throughput. Where a bounce also sampled the lights and environment directly, scatter_pdf
+  // holds the density its material chose the next ray with, so that light found by that ray
+  // is weighted against the direct estimate.
+  fn trace(&self, r: &ray::Ray, world: &dyn hittable::Hittable) -> (vector::Vector, bool) {
+    let mut colour = vector::Vector::new(0.0, 0.0, 0.0);
+    let mut throughput = vector::Vector::new(1.0, 1.0, 1.0);
+    let mut ray = ray::Ray::with_time(r.orig, r.dir, r.time);
+    let mut scatter_pdf: Option<f64> = None;
+    let mut hit_anything = false;
+
+    for bounce in 0..self.max_depth {
+      let mut hit_rec = hittable::HitRecord::new_empty(); // No material yet
+      if !world.hit(
+        &ray,
+        interval::Interval::new(0.001, utility::INFINITY),
+        &mut hit_rec,
+      ) {
+        let radiance = self.environment.radiance(&ray.dir);
+        let weight = scatter_pdf.map_or(1.0, |pdf| {
+          power_heuristic(pdf, self.environment.pdf(&ray.dir))
+        });
+        colour = colour + throughput * radiance * weight;
+        break;
+      }
+      hit_anything |= bounce == 0;
+      hit_rec.normal = hit_rec.mat.shading_normal(&hit_rec);
+
+      let weight = scatter_pdf.map_or(1.0, |pdf| {
+        power_heuristic(pdf, self.lights.pdf_value(&ray.orig, &ray.dir))
+      });
+      colour = colour + throughput * hit_rec.mat.emitted(&ray, &hit_rec) * weight;
+      let Some(sample) = hit_rec.mat.sample(&ray, &hit_rec) else {
+        break;
+      };
+
+      if sample.delta {
+        scatter_pdf = None;
+      } else {
+        let direct = self.sample_lights(&ray, &hit_rec, world)
+          + self.sample_environment(&ray, &hit_rec, world);
+        colour = colour + throughput * direct;
+        scatter_pdf = Some(sample.pdf);
+      }
+      throughput = throughput * sample.attenuation;
+      ray = ray::Ray::with_time(hit_rec.point, sample.direction, ray.time);
+
+      if bounce + 1 >= self.roulette_depth {
+        let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+        if survival <= utility::random_df() {
+          break;
+        }
+        throughput = throughput / survival;
+      }
+    }
+
+    (colour, hit_anything)
+  }
+
+  // Light reaching the hit point straight from a random point on one of the lights, weighted
+  // against the chance of the material scattering that way (multiple importance sampling)
+  fn sample_lights(
+    &self,
+    r: &ray::Ray,
+    hit_rec: &hittable::HitRecord,
+    world: &dyn hittable::Hittable,
+  ) -> vector::Vector {
+    let black = vector::Vector::new(0.0, 0.0, 0.0);
+    if self.lights.objects.is_empty() {
+      return black;
+    }
+    let dir = self.lights.random(&hit_rec.point);
+    let light_pdf = self.lights.pdf_value(&hit_rec.point, &dir);
+    if light_pdf <= 0.0 {
+      return black;
+    }
+    let scatter_pdf = hit_rec.mat.pdf(r, hit_rec, &dir);
+    if scatter_pdf <= 0.0 {
+      return black;
+    }
+    let shadow = ray::Ray::with_time(hit_rec.point, dir, r.time);
+
+    // Whatever the shadow ray reaches first, the light itself or something in the way
+    let mut light_rec = hittable::HitRecord::new_empty();
+    if !world.hit(
+      &shadow,
+      interval::Interval::new(0.001, utility::INFINITY),
+      &mut light_rec,
+    ) {
+      return black;
     }
-    let mut hit_rec = hittable::HitRecord::new_empty(); // No material yet
+    let emitted = light_rec.mat.emitted(&shadow, &light_rec);
+
+    let weight = power_heuristic(light_pdf, scatter_pdf);
+    hit_rec.mat.eval(r, hit_rec, &dir) * emitted * (weight / light_pdf)
+  }
+
+  // Light reaching the hit point straight from a direction picked by the environment, weighted
+  // against the chance of the material scattering that way (multiple importance sampling)
+  fn sample_environment(
+    &self,
+    r: &ray::Ray,
+    hit_rec: &hittable::HitRecord,
+    world: &dyn hittable::Hittable,
+  ) -> vector::Vector {
+    let black = vector::Vector::new(0.0, 0.0, 0.0);
+    let Some((dir, light_pdf)) = self.environment.sample() else {
+      return black;
+    };
+    let scatter_pdf = hit_rec.mat.pdf(r, hit_rec, &dir);
+    if scatter_pdf <= 0.0 {
+      return black;
+    }
+    let shadow = ray::Ray::with_time(hit_rec.point, dir, r.time);
+    let mut blocker = hittable::HitRecord::new_empty();
     if world.hit(
-      r,
+      &shadow,
       interval::Interval::new(0.001, utility::INFINITY),
-      &mut hit_rec,
+      &mut blocker,
     ) {
-      let mut attenuation = vector::Vector::new(0.0, 0.0, 0.0);
-      let mut scattered = ray::Ray::new(
-        vector::Vector::new(0.0, 0.0, 0.0),
-        vector::Vector::new(0.0, 0.0, 0.0),
-      );
-      if hit_rec
-        .mat
-        .scatter(r, &hit_rec, &mut attenuation, &mut scattered)
-      {
-        return attenuation * Camera::ray_colour(&scattered, depth - 1, world);
+      return black;
+    }
+
+    let weight = power_heuristic(light_pdf, scatter_pdf);
+    hit_rec.mat.eval(r, hit_rec, &dir) * self.environment.radiance(&dir) * (weight / light_pdf)
+  }
+}
+
+// Veach's power heuristic (with beta = 2): the weight given to a sample from the strategy with
+// density pdf, when the other strategy would have picked it with density other_pdf
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+  let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+  if a + b == 0.0 {
+    0.0
+  } else {
+    a / (a + b)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lib::{material, quad};
+  use std::sync;
+
+  // Camera at the origin looking down -z, seeing nothing but a black background
+  fn camera(image_width: usize, samples_per_pixel: u32) -> Camera {
+    let mut camera = Camera::new(
+      1.0,
+      image_width,
+      samples_per_pixel,
+      10,
+      vector::Vector::new(0.0, 0.0, 0.0),
+      vector::Vector::new(0.0, 0.0, -1.0),
+      vector::Vector::new(0.0, 1.0, 0.0),
+      90.0,
+      0.0,
+      1.0,
+    );
+    camera.set_progress(Box::new(progress::Silent));
+    camera.set_environment(Box::new(environment::Solid {
+      colour: vector::Vector::new(0.0, 0.0, 0.0),
+    }));
+    camera
+  }
+
+  #[test]
+  fn averages_exactly_samples_per_pixel_samples() {
+    // A light filling the whole view, so every sample sees radiance 1
+    let light = sync::Arc::new(material::DiffuseLight {
+      colour: vector::Vector::new(1.0, 1.0, 1.0),
+      intensity: 1.0,
+    });
+    let mut world = hittable_list::HittableList::new_empty();
+    world.add(sync::Arc::new(quad::Quad::new(
+      vector::Vector::new(-10.0, -10.0, -1.0),
+      vector::Vector::new(20.0, 0.0, 0.0),
+      vector::Vector::new(0.0, 20.0, 0.0),
+      light,
+    )));
+
+    let image = camera(4, 4).render(&world);
+    for y in 0..4 {
+      for x in 0..4 {
+        assert_eq!(image.get(x, y), vector::Vector::new(1.0, 1.0, 1.0));
+        assert_eq!(image.get_alpha(x, y), 1.0);
       }
-      return vector::Vector::new(0.0, 0.0, 0.0);
-    } else {
-      let unit_dir = vector::Vector::unit_vector(&r.dir);
-      let a = (unit_dir.y + 1.0) * 0.5;
+    }
+  }
+
+  // Mean over pixels of each pixel's variance across renders with different seeds
+  fn pixel_variance(camera: &mut Camera, world: &dyn hittable::Hittable, renders: u64) -> f64 {
+    let images: Vec<_> = (0..renders)
+      .map(|seed| {
+        camera.set_seed(Some(seed));
+        camera.render(world)
+      })
+      .collect();
+    let (width, height) = (camera.image_width, camera.image_height);
+
+    let mut total = 0.0;
+    for y in 0..height {
+      for x in 0..width {
+        let values: Vec<f64> = images.iter().map(|image| image.get(x, y).y).collect();
+        let mean = values.iter().sum::<f64>() / renders as f64;
+        total += values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (renders - 1) as f64;
+      }
+    }
+    total / (width * height) as f64
+  }
 
-      vector::Vector::new(1.0, 1.0, 1.0) * (1.0 - a) + vector::Vector::new(0.5, 0.7, 1.0) * a
+  #[test]
+  fn light_sampling_lowers_variance() {
+    // A small bright light hanging over a diffuse floor, seen from below the light
+    let light: sync::Arc<dyn hittable::Hittable> = sync::Arc::new(quad::Quad::new(
+      vector::Vector::new(-0.1, 2.0, -0.1),
+      vector::Vector::new(0.2, 0.0, 0.0),
+      vector::Vector::new(0.0, 0.0, 0.2),
+      sync::Arc::new(material::DiffuseLight {
+        colour: vector::Vector::new(1.0, 1.0, 1.0),
+        intensity: 50.0,
+      }),
+    ));
+    let mut world = hittable_list::HittableList::new_empty();
+    world.add(sync::Arc::new(quad::Quad::new(
+      vector::Vector::new(-5.0, 0.0, 5.0),
+      vector::Vector::new(10.0, 0.0, 0.0),
+      vector::Vector::new(0.0, 0.0, -10.0),
+      sync::Arc::new(material::Lambertian::new(vector::Vector::new(
+        0.5, 0.5, 0.5,
+      ))),
+    )));
+    world.add(light.clone());
+
+    let mut camera = camera(8, 1);
+    camera.set_threads(1);
+    camera.set_view(
+      vector::Vector::new(0.0, 1.0, 0.0),
+      vector::Vector::new(0.0, 0.0, 0.0),
+      vector::Vector::new(0.0, 0.0, -1.0),
+      60.0,
+    );
+
+    // Without lights to sample, paths only find the light by bouncing into it
+    let bsdf_only = pixel_variance(&mut camera, &world, 32);
+    let mut lights = hittable_list::HittableList::new_empty();
+    lights.add(light);
+    camera.set_lights(lights);
+    let with_lights = pixel_variance(&mut camera, &world, 32);
+
+    assert!(
+      with_lights < bsdf_only / 10.0,
+      "variance {with_lights} with light sampling, {bsdf_only} without"
+    );
+  }
+
+  #[test]
+  fn seeded_renders_match_across_thread_counts() {
+    // Big enough for several tiles, so threads pick them up in different orders
+    let mut world = hittable_list::HittableList::new_empty();
+    world.add(sync::Arc::new(quad::Quad::new(
+      vector::Vector::new(-2.0, -1.0, 0.0),
+      vector::Vector::new(4.0, 0.0, 0.0),
+      vector::Vector::new(0.0, 0.0, -4.0),
+      sync::Arc::new(material::Lambertian::new(vector::Vector::new(
+        0.5, 0.5, 0.5,
+      ))),
+    )));
+    world.add(sync::Arc::new(quad::Cuboid::new(
+      vector::Vector::new(-0.5, -1.0, -2.0),
+      vector::Vector::new(0.5, 0.0, -1.5),
+      sync::Arc::new(material::Metal::new(
+        vector::Vector::new(0.8, 0.8, 0.8),
+        0.3,
+      )),
+    )));
+
+    let mut camera = camera(40, 4);
+    camera.set_environment(Box::new(environment::Solid {
+      colour: vector::Vector::new(0.7, 0.8, 1.0),
+    }));
+    camera.set_seed(Some(3));
+    let render = |camera: &mut Camera, threads: usize| {
+      camera.set_threads(threads);
+      camera.render(&world)
+    };
+    let single = render(&mut camera, 1);
+    let several = render(&mut camera, 7);
+
+    for y in 0..camera.image_height {
+      for x in 0..camera.image_width {
+        assert_eq!(single.get(x, y), several.get(x, y), "pixel ({x}, {y})");
+        assert_eq!(single.get_alpha(x, y), several.get_alpha(x, y));
+      }
     }
   }
 }
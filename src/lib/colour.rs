@@ -1,6 +1,106 @@
 use crate::lib::interval;
 use crate::lib::vector::Vector;
 
+// Curve compressing linear radiance in [0, inf) into the displayable [0, 1] range
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ToneMap {
+  // Hard clamp at 1, as before tone mapping existed
+  None,
+  // x / (1 + x), never quite reaches white
+  Reinhard,
+  // Reinhard scaled so that white_point maps exactly to 1
+  ReinhardExtended { white_point: f64 },
+  // Narkowicz's fit of the ACES filmic reference curve
+  Aces,
+  // John Hable's filmic curve from Uncharted 2
+  Hable,
+}
+
+// Encoding from linear light to the values stored in an 8-bit image
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TransferFunction {
+  // The piecewise sRGB curve, which is what viewers assume for untagged images
+  Srgb,
+  // Pure power law, encoding x as x^(1/gamma)
+  Gamma(f64),
+  // Values are stored unchanged
+  Linear,
+}
+
+// Everything applied to a linear pixel before it is quantized
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DisplayTransform {
+  // Brightness adjustment in stops, each one doubling the radiance
+  pub exposure: f64,
+  pub tone_map: ToneMap,
+  pub transfer: TransferFunction,
+}
+
+impl Default for DisplayTransform {
+  fn default() -> Self {
+    DisplayTransform {
+      exposure: 0.0,
+      tone_map: ToneMap::None,
+      transfer: TransferFunction::Srgb,
+    }
+  }
+}
+
+impl DisplayTransform {
+  // Exposed and tone mapped linear colour, not yet encoded
+  pub fn apply(&self, v: &Vector) -> Vector {
+    let exposed = *v * 2f64.powf(self.exposure);
+    Vector::new(
+      self.tone_map.apply(exposed.x),
+      self.tone_map.apply(exposed.y),
+      self.tone_map.apply(exposed.z),
+    )
+  }
+}
+
+impl TransferFunction {
+  pub fn encode(&self, linear_component: f64) -> f64 {
+    match *self {
+      TransferFunction::Srgb => linear_to_srgb(linear_component),
+      TransferFunction::Gamma(gamma) => linear_component.max(0.0).powf(1.0 / gamma),
+      TransferFunction::Linear => linear_component,
+    }
+  }
+}
+
+impl ToneMap {
+  pub fn apply(&self, x: f64) -> f64 {
+    let x = x.max(0.0);
+    match *self {
+      ToneMap::None => x,
+      ToneMap::Reinhard => x / (1.0 + x),
+      ToneMap::ReinhardExtended { white_point } => {
+        x * (1.0 + x / (white_point * white_point)) / (1.0 + x)
+      }
+      ToneMap::Aces => {
+        let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+        (x * (a * x + b)) / (x * (c * x + d) + e)
+      }
+      ToneMap::Hable => {
+        let exposure_bias = 2.0;
+        let white_point = 11.2;
+        hable_partial(x * exposure_bias) / hable_partial(white_point)
+      }
+    }
+  }
+}
+
+fn hable_partial(x: f64) -> f64 {
+  let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+  ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+// Relative luminance of a linear Rec. 709 / sRGB colour
+pub fn luminance(v: &Vector) -> f64 {
+  0.2126 * v.x + 0.7152 * v.y + 0.0722 * v.z
+}
+
+#[allow(dead_code)]
 pub fn linear_to_gamma(linear_component: f64) -> f64 {
   if linear_component > 0.0 {
     return linear_component.sqrt();
@@ -8,11 +108,29 @@ pub fn linear_to_gamma(linear_component: f64) -> f64 {
   0.0
 }
 
-pub fn get_colour(v: &Vector) -> (u8, u8, u8) {
+// The sRGB opto-electronic transfer function (IEC 61966-2-1)
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+  if linear_component <= 0.0031308 {
+    return 12.92 * linear_component.max(0.0);
+  }
+  1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+}
+
+// Inverse of linear_to_srgb, for reading colours from ordinary images
+pub fn srgb_to_linear(encoded_component: f64) -> f64 {
+  if encoded_component <= 0.04045 {
+    return encoded_component.max(0.0) / 12.92;
+  }
+  ((encoded_component + 0.055) / 1.055).powf(2.4)
+}
+
+pub fn get_colour(v: &Vector, display: &DisplayTransform) -> (u8, u8, u8) {
+  let v = display.apply(v);
+  let encode = |c| display.transfer.encode(c);
   let intensity = interval::Interval::new(0.0, 0.999);
   (
-    (256.0 * intensity.clamp(linear_to_gamma(v.x))) as u8,
-    (256.0 * intensity.clamp(linear_to_gamma(v.y))) as u8,
-    (256.0 * intensity.clamp(linear_to_gamma(v.z))) as u8,
+    (256.0 * intensity.clamp(encode(v.x))) as u8,
+    (256.0 * intensity.clamp(encode(v.y))) as u8,
+    (256.0 * intensity.clamp(encode(v.z))) as u8,
   )
 }
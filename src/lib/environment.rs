@@ -0,0 +1,222 @@
+use crate::lib::vector::Vector;
+use crate::lib::{colour, utility};
+use std::path;
+
+// Radiance arriving from infinitely far away, seen by every ray that leaves the scene
+pub trait Environment: Send + Sync {
+  // dir need not be unit length
+  fn radiance(&self, dir: &Vector) -> Vector;
+
+  // Picks a unit direction to look for light in, along with the probability density of having
+  // picked it per unit solid angle. Environments that aren't worth sampling directly, because
+  // light arrives roughly evenly from everywhere, return None.
+  fn sample(&self) -> Option<(Vector, f64)> {
+    None
+  }
+
+  // The density sample would pick dir with, 0 for environments that aren't sampled
+  fn pdf(&self, _dir: &Vector) -> f64 {
+    0.0
+  }
+}
+
+// The same colour in every direction. Black gives a scene lit only by its own lights.
+pub struct Solid {
+  pub colour: Vector,
+}
+
+impl Environment for Solid {
+  fn radiance(&self, _dir: &Vector) -> Vector {
+    self.colour
+  }
+}
+
+// Blends linearly from bottom, looking straight down the up axis, to top, looking straight up it
+pub struct Gradient {
+  pub bottom: Vector,
+  pub top: Vector,
+  pub up: Vector,
+}
+
+impl Gradient {
+  // White fading to light blue overhead, the background from Ray Tracing in One Weekend
+  pub fn sky() -> Gradient {
+    Gradient {
+      bottom: Vector::new(1.0, 1.0, 1.0),
+      top: Vector::new(0.5, 0.7, 1.0),
+      up: Vector::new(0.0, 1.0, 0.0),
+    }
+  }
+}
+
+impl Environment for Gradient {
+  fn radiance(&self, dir: &Vector) -> Vector {
+    let a = (dir.unit_vector().dot(&self.up.unit_vector()) + 1.0) * 0.5;
+    self.bottom * (1.0 - a) + self.top * a
+  }
+}
+
+// Equirectangular (latitude-longitude) image of the surroundings, such as an HDRI probe. The
+// centre of the image is straight ahead along -z, the top row straight up along +y.
+//
+// Directions are importance sampled in proportion to the luminance of their pixel, so that
+// small bright features like the sun are found by direct light sampling rather than by luck.
+pub struct Map {
+  width: usize,
+  height: usize,
+  pixels: Vec<Vector>,
+  // Turn about the y axis in radians, anticlockwise seen from above
+  rotation: f64,
+  intensity: f64,
+  // Probability of sampling each pixel, row by row
+  probabilities: Vec<f64>,
+  // Cumulative distributions for picking a row, then a pixel within that row
+  row_cdf: Vec<f64>,
+  column_cdfs: Vec<f64>,
+}
+
+impl Map {
+  // Loads an .hdr or .exr panorama, rotated by rotation degrees about the y axis and with its
+  // radiance scaled by intensity
+  pub fn load(
+    path: impl AsRef<path::Path>,
+    rotation: f64,
+    intensity: f64,
+  ) -> Result<Map, image::ImageError> {
+    let image = image::open(path)?.into_rgb32f();
+    let pixels = image
+      .pixels()
+      .map(|p| Vector::new(p[0].into(), p[1].into(), p[2].into()))
+      .collect();
+    Ok(Map::new(
+      image.width() as usize,
+      image.height() as usize,
+      pixels,
+      rotation,
+      intensity,
+    ))
+  }
+
+  // Panics unless there are width * height pixels
+  pub fn new(
+    width: usize,
+    height: usize,
+    pixels: Vec<Vector>,
+    rotation: f64,
+    intensity: f64,
+  ) -> Map {
+    assert_eq!(pixels.len(), width * height);
+
+    // Rows near the poles cover less solid angle, so they're weighted down by sin(theta)
+    let mut weights: Vec<f64> = pixels
+      .iter()
+      .enumerate()
+      .map(|(i, p)| {
+        let theta = ((i / width) as f64 + 0.5) / height as f64 * utility::PI;
+        colour::luminance(p).max(0.0) * theta.sin()
+      })
+      .collect();
+    let total: f64 = weights.iter().sum();
+    if total > 0.0 {
+      weights.iter_mut().for_each(|w| *w /= total);
+    }
+
+    let mut row_cdf = Vec::with_capacity(height);
+    let mut column_cdfs = Vec::with_capacity(width * height);
+    let mut cumulative = 0.0;
+    for row in weights.chunks(width) {
+      let row_total: f64 = row.iter().sum();
+      cumulative += row_total;
+      row_cdf.push(cumulative);
+
+      let mut row_cumulative = 0.0;
+      for w in row {
+        row_cumulative += w;
+        column_cdfs.push(if row_total > 0.0 {
+          row_cumulative / row_total
+        } else {
+          0.0
+        });
+      }
+    }
+
+    Map {
+      width,
+      height,
+      pixels,
+      rotation: utility::deg_to_rad(rotation),
+      intensity,
+      probabilities: weights,
+      row_cdf,
+      column_cdfs,
+    }
+  }
+
+  // Index of the pixel seen in direction dir, which must be unit length, and the polar angle
+  // of dir from +y
+  fn pixel(&self, dir: &Vector) -> (usize, f64) {
+    let dir = rotate_y(dir, -self.rotation);
+    let theta = dir.y.clamp(-1.0, 1.0).acos();
+    let phi = dir.x.atan2(-dir.z) + utility::PI;
+    let u = phi / (2.0 * utility::PI);
+    let v = theta / utility::PI;
+    let x = ((u * self.width as f64) as usize).min(self.width - 1);
+    let y = ((v * self.height as f64) as usize).min(self.height - 1);
+    (y * self.width + x, theta)
+  }
+
+  // Converts the probability of picking a pixel into a density per unit solid angle
+  fn solid_angle_pdf(&self, probability: f64, theta: f64) -> f64 {
+    let sin_theta = theta.sin();
+    if sin_theta <= 0.0 {
+      return 0.0;
+    }
+    probability * (self.width * self.height) as f64 / (2.0 * utility::PI * utility::PI * sin_theta)
+  }
+}
+
+impl Environment for Map {
+  fn radiance(&self, dir: &Vector) -> Vector {
+    self.pixels[self.pixel(&dir.unit_vector()).0] * self.intensity
+  }
+
+  fn sample(&self) -> Option<(Vector, f64)> {
+    if self.row_cdf.last().is_none_or(|&total| total <= 0.0) {
+      return None;
+    }
+
+    let y = pick(&self.row_cdf, utility::random_df());
+    let row = &self.column_cdfs[y * self.width..(y + 1) * self.width];
+    let x = pick(row, utility::random_df());
+
+    // Uniformly within the chosen pixel
+    let u = (x as f64 + utility::random_df()) / self.width as f64;
+    let v = (y as f64 + utility::random_df()) / self.height as f64;
+    let theta = v * utility::PI;
+    let phi = u * 2.0 * utility::PI - utility::PI;
+    let dir = Vector::new(
+      theta.sin() * phi.sin(),
+      theta.cos(),
+      -theta.sin() * phi.cos(),
+    );
+
+    let pdf = self.solid_angle_pdf(self.probabilities[y * self.width + x], theta);
+    (pdf > 0.0).then(|| (rotate_y(&dir, self.rotation), pdf))
+  }
+
+  fn pdf(&self, dir: &Vector) -> f64 {
+    let (pixel, theta) = self.pixel(&dir.unit_vector());
+    self.solid_angle_pdf(self.probabilities[pixel], theta)
+  }
+}
+
+// First index whose cumulative value exceeds x
+fn pick(cdf: &[f64], x: f64) -> usize {
+  let scaled = x * cdf.last().copied().unwrap_or(0.0);
+  cdf.partition_point(|&c| c <= scaled).min(cdf.len() - 1)
+}
+
+fn rotate_y(v: &Vector, angle: f64) -> Vector {
+  let (sin, cos) = angle.sin_cos();
+  Vector::new(cos * v.x + sin * v.z, v.y, cos * v.z - sin * v.x)
+}
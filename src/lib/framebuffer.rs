@@ -0,0 +1,219 @@
+use crate::lib::{colour, vector};
+use image::error::{EncodingError, ImageFormatHint};
+use image::{ImageBuffer, ImageError, ImageFormat, Rgb, Rgb32FImage, RgbImage, Rgba, Rgba32FImage};
+use std::{error, fmt, fs, io, path};
+
+// Linear RGB radiance for every pixel of a rendered image, stored row by row. Alongside the
+// colour each pixel has an alpha, the fraction of its camera rays that hit geometry rather
+// than the background.
+pub struct Framebuffer {
+  width: usize,
+  height: usize,
+  pixels: Vec<vector::Vector>,
+  alpha: Vec<f64>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+  Io(io::Error),
+  Encode(ImageError),
+  // The output file extension isn't one of png, exr or hdr
+  UnsupportedFormat(String),
+}
+
+impl fmt::Display for SaveError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SaveError::Io(e) => write!(f, "could not write image: {e}"),
+      SaveError::Encode(e) => write!(f, "could not encode image: {e}"),
+      SaveError::UnsupportedFormat(ext) => write!(
+        f,
+        "unsupported image format `{ext}`, expected one of png, exr or hdr"
+      ),
+    }
+  }
+}
+
+impl error::Error for SaveError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      SaveError::Io(e) => Some(e),
+      SaveError::Encode(e) => Some(e),
+      SaveError::UnsupportedFormat(_) => None,
+    }
+  }
+}
+
+impl From<ImageError> for SaveError {
+  fn from(e: ImageError) -> Self {
+    match e {
+      ImageError::IoError(e) => SaveError::Io(e),
+      e => SaveError::Encode(e),
+    }
+  }
+}
+
+impl From<io::Error> for SaveError {
+  fn from(e: io::Error) -> Self {
+    SaveError::Io(e)
+  }
+}
+
+impl From<png::EncodingError> for SaveError {
+  fn from(e: png::EncodingError) -> Self {
+    match e {
+      png::EncodingError::IoError(e) => SaveError::Io(e),
+      e => SaveError::Encode(ImageError::Encoding(EncodingError::new(
+        ImageFormatHint::Exact(ImageFormat::Png),
+        e,
+      ))),
+    }
+  }
+}
+
+impl Framebuffer {
+  // Black image of the given size
+  pub fn new(width: usize, height: usize) -> Framebuffer {
+    Framebuffer {
+      width,
+      height,
+      pixels: vec![vector::Vector::new(0.0, 0.0, 0.0); width * height],
+      alpha: vec![1.0; width * height],
+    }
+  }
+
+  pub fn get(&self, x: usize, y: usize) -> vector::Vector {
+    self.pixels[y * self.width + x]
+  }
+
+  pub fn set(&mut self, x: usize, y: usize, colour: vector::Vector) {
+    self.pixels[y * self.width + x] = colour;
+  }
+
+  pub fn get_alpha(&self, x: usize, y: usize) -> f64 {
+    self.alpha[y * self.width + x]
+  }
+
+  pub fn set_alpha(&mut self, x: usize, y: usize, alpha: f64) {
+    self.alpha[y * self.width + x] = alpha;
+  }
+
+  // Tone maps, gamma encodes and quantizes the image to 8 bits per channel
+  pub fn to_rgb_image(&self, display: &colour::DisplayTransform) -> RgbImage {
+    ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+      let (r, g, b) = colour::get_colour(&self.get(x as usize, y as usize), display);
+      Rgb([r, g, b])
+    })
+  }
+
+  // Linear radiance as 32-bit floats, without any clamping
+  pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+    ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+      let c = self.get(x as usize, y as usize);
+      Rgb([c.x as f32, c.y as f32, c.z as f32])
+    })
+  }
+
+  pub fn to_rgba32f_image(&self) -> Rgba32FImage {
+    ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+      let (x, y) = (x as usize, y as usize);
+      let c = self.get(x, y);
+      Rgba([
+        c.x as f32,
+        c.y as f32,
+        c.z as f32,
+        self.get_alpha(x, y) as f32,
+      ])
+    })
+  }
+
+  // Writes the image in the format given by the file extension: png, exr or hdr. The display
+  // transform only applies to png; the other formats keep the linear radiance.
+  pub fn save(
+    &self,
+    path: impl AsRef<path::Path>,
+    display: &colour::DisplayTransform,
+  ) -> Result<(), SaveError> {
+    let path = path.as_ref();
+    match check_extension(path)? {
+      ImageFormat::OpenExr => self.save_exr(path, false),
+      ImageFormat::Hdr => self.save_hdr(path),
+      _ => self.save_png(path, display),
+    }
+  }
+
+  // 8-bit PNG tagged with the display transform's transfer function, so that viewers decode it
+  // the same way it was encoded: an sRGB chunk for sRGB, otherwise a gAMA chunk
+  pub fn save_png(
+    &self,
+    path: impl AsRef<path::Path>,
+    display: &colour::DisplayTransform,
+  ) -> Result<(), SaveError> {
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    match display.transfer {
+      colour::TransferFunction::Srgb => {
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        // Fallback gAMA and cHRM values from the PNG spec for decoders that ignore sRGB
+        encoder.set_source_gamma(png::ScaledFloat::from_scaled(45455));
+        encoder.set_source_chromaticities(png::SourceChromaticities::new(
+          (0.3127, 0.3290),
+          (0.64, 0.33),
+          (0.30, 0.60),
+          (0.15, 0.06),
+        ));
+      }
+      colour::TransferFunction::Gamma(gamma) => {
+        encoder.set_source_gamma(png::ScaledFloat::new(1.0 / gamma as f32));
+      }
+      colour::TransferFunction::Linear => {
+        encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+      }
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(self.to_rgb_image(display).as_raw())?;
+    writer.finish()?;
+    Ok(())
+  }
+
+  // OpenEXR with float32 channels, RGBA when with_alpha is set
+  pub fn save_exr(&self, path: impl AsRef<path::Path>, with_alpha: bool) -> Result<(), SaveError> {
+    if with_alpha {
+      self
+        .to_rgba32f_image()
+        .save_with_format(path, ImageFormat::OpenExr)?;
+    } else {
+      self
+        .to_rgb32f_image()
+        .save_with_format(path, ImageFormat::OpenExr)?;
+    }
+    Ok(())
+  }
+
+  // Radiance RGBE
+  pub fn save_hdr(&self, path: impl AsRef<path::Path>) -> Result<(), SaveError> {
+    self
+      .to_rgb32f_image()
+      .save_with_format(path, ImageFormat::Hdr)?;
+    Ok(())
+  }
+}
+
+// The format Framebuffer::save would write to path, so it can be checked before rendering
+pub fn check_extension(path: impl AsRef<path::Path>) -> Result<ImageFormat, SaveError> {
+  let ext = path
+    .as_ref()
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or("")
+    .to_lowercase();
+  match ext.as_str() {
+    "png" => Ok(ImageFormat::Png),
+    "exr" => Ok(ImageFormat::OpenExr),
+    "hdr" => Ok(ImageFormat::Hdr),
+    _ => Err(SaveError::UnsupportedFormat(ext)),
+  }
+}
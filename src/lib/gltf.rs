@@ -0,0 +1,425 @@
+use crate::lib::mesh::{self, Face, FaceVertex, MeshData};
+use crate::lib::vector::Vector;
+use crate::lib::{camera, hittable_list, material, scene};
+use ::gltf::camera::Projection;
+use ::gltf::mesh::Mode;
+use std::collections::HashMap;
+use std::{error, fmt, fs, path, sync};
+
+// Extensions that change how the scene is built. Any other extension the file uses is
+// ignored with a warning, even one it marks as required.
+const HANDLED_EXTENSIONS: &[&str] = &[
+  "KHR_materials_emissive_strength",
+  "KHR_materials_ior",
+  "KHR_materials_transmission",
+];
+
+// A scene loaded from glTF, along with anything in the file that couldn't be represented
+pub struct Import {
+  pub scene: scene::Scene,
+  pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct GltfError {
+  pub path: path::PathBuf,
+  pub error: ::gltf::Error,
+}
+
+impl fmt::Display for GltfError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.path.display(), self.error)
+  }
+}
+
+impl error::Error for GltfError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    Some(&self.error)
+  }
+}
+
+// Loads the default scene of a glTF 2.0 file, .gltf or .glb, with its buffers resolved
+// relative to the file. Every triangle primitive becomes a mesh in world space, with the
+// node transforms applied.
+//
+// Metallic-roughness materials are mapped onto the closest of the renderer's materials:
+//   - emissive ones become DiffuseLight, scaled by any KHR_materials_emissive_strength
+//   - transmissive or blended, partly transparent ones become Dialetric with the KHR_materials_ior
+//     index, 1.5 by default
+//   - ones with metallic >= 0.5 become Metal with the base colour as albedo and fuzz roughness
+//   - everything else becomes Lambertian with the base colour as albedo
+// Textures are ignored, leaving just the colour factors.
+//
+// The first perspective camera in the scene becomes the camera. Without one the scene gets
+// Camera::default.
+pub fn load(path: impl AsRef<path::Path>) -> Result<Import, GltfError> {
+  let path = path.as_ref();
+  let error = |error| GltfError {
+    path: path.to_path_buf(),
+    error,
+  };
+
+  let bytes = fs::read(path).map_err(|e| error(::gltf::Error::Io(e)))?;
+  let (mut root, blob) = if bytes.starts_with(b"glTF") {
+    let glb = ::gltf::binary::Glb::from_slice(&bytes).map_err(error)?;
+    let root = ::gltf::json::Root::from_slice(&glb.json)
+      .map_err(|e| error(::gltf::Error::Deserialize(e)))?;
+    (root, glb.bin.map(|bin| bin.into_owned()))
+  } else {
+    let root =
+      ::gltf::json::Root::from_slice(&bytes).map_err(|e| error(::gltf::Error::Deserialize(e)))?;
+    (root, None)
+  };
+
+  let mut importer = Importer {
+    warnings: Vec::new(),
+    materials: HashMap::new(),
+    world: hittable_list::HittableList::new_empty(),
+    camera: None,
+  };
+  for name in &root.extensions_used {
+    if !HANDLED_EXTENSIONS.contains(&name.as_str()) {
+      importer.warn(format!(
+        "extension {name} is not supported and will be ignored"
+      ));
+    }
+  }
+  // The gltf crate refuses files requiring extensions it doesn't know, but the scene can
+  // still be rendered approximately without them
+  root.extensions_required.clear();
+
+  let document = ::gltf::Document::from_json(root).map_err(error)?;
+  let dir = path.parent().unwrap_or(path::Path::new(""));
+  let buffers = ::gltf::import_buffers(&document, Some(dir), blob).map_err(error)?;
+
+  match document
+    .default_scene()
+    .or_else(|| document.scenes().next())
+  {
+    Some(scene) => {
+      for node in scene.nodes() {
+        importer.visit(&node, &IDENTITY, &buffers);
+      }
+    }
+    None => importer.warn("file has no scenes".to_string()),
+  }
+
+  let camera = match importer.camera.take() {
+    Some(camera) => camera,
+    None => {
+      importer.warn("file has no perspective camera, using the default view".to_string());
+      camera::Camera::default()
+    }
+  };
+  Ok(Import {
+    scene: scene::Scene {
+      camera,
+      world: importer.world,
+    },
+    warnings: importer.warnings,
+  })
+}
+
+// Column-major 4x4 matrix, as glTF stores them
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+  [1.0, 0.0, 0.0, 0.0],
+  [0.0, 1.0, 0.0, 0.0],
+  [0.0, 0.0, 1.0, 0.0],
+  [0.0, 0.0, 0.0, 1.0],
+];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+  let mut m = [[0.0; 4]; 4];
+  for (col, b_col) in b.iter().enumerate() {
+    for row in 0..4 {
+      m[col][row] = (0..4).map(|k| a[k][row] * b_col[k]).sum();
+    }
+  }
+  m
+}
+
+fn transform_point(m: &Matrix, p: Vector) -> Vector {
+  transform_vector(m, p) + Vector::new(m[3][0], m[3][1], m[3][2])
+}
+
+fn transform_vector(m: &Matrix, v: Vector) -> Vector {
+  Vector::new(
+    m[0][0] * v.x + m[1][0] * v.y + m[2][0] * v.z,
+    m[0][1] * v.x + m[1][1] * v.y + m[2][1] * v.z,
+    m[0][2] * v.x + m[1][2] * v.y + m[2][2] * v.z,
+  )
+}
+
+// Normals transform by the inverse transpose of the upper 3x3. The cofactor matrix is that
+// scaled by the determinant, which only matters up to sign since normals are renormalized.
+fn normal_matrix(m: &Matrix) -> (Matrix, f64) {
+  let a = |row: usize, col: usize| m[col][row];
+  let cofactor = |row: usize, col: usize| {
+    let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+    let (c0, c1) = ((col + 1) % 3, (col + 2) % 3);
+    a(r0, c0) * a(r1, c1) - a(r0, c1) * a(r1, c0)
+  };
+  let det = (0..3).map(|col| a(0, col) * cofactor(0, col)).sum::<f64>();
+
+  let mut n = IDENTITY;
+  for (col, n_col) in n.iter_mut().enumerate().take(3) {
+    for (row, value) in n_col.iter_mut().enumerate().take(3) {
+      *value = cofactor(row, col) * det.signum();
+    }
+  }
+  (n, det)
+}
+
+struct Importer {
+  warnings: Vec<String>,
+  // Converted materials by index, with None for the glTF default material
+  materials: HashMap<Option<usize>, sync::Arc<dyn material::Material>>,
+  world: hittable_list::HittableList,
+  camera: Option<camera::Camera>,
+}
+
+impl Importer {
+  fn warn(&mut self, warning: String) {
+    if !self.warnings.contains(&warning) {
+      self.warnings.push(warning);
+    }
+  }
+
+  fn visit(&mut self, node: &::gltf::Node, parent: &Matrix, buffers: &[::gltf::buffer::Data]) {
+    let local = node.transform().matrix().map(|col| col.map(f64::from));
+    let world = multiply(parent, &local);
+
+    if let Some(mesh) = node.mesh() {
+      for primitive in mesh.primitives() {
+        self.add_primitive(&primitive, &world, buffers);
+      }
+    }
+    if let Some(camera) = node.camera() {
+      self.set_camera(&camera, &world);
+    }
+    for child in node.children() {
+      self.visit(&child, &world, buffers);
+    }
+  }
+
+  fn add_primitive(
+    &mut self,
+    primitive: &::gltf::Primitive,
+    world: &Matrix,
+    buffers: &[::gltf::buffer::Data],
+  ) {
+    if primitive.mode() != Mode::Triangles {
+      self.warn(format!(
+        "{:?} primitives are not supported and will be skipped",
+        primitive.mode()
+      ));
+      return;
+    }
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()][..]));
+    let Some(positions) = reader.read_positions() else {
+      self.warn("primitive without positions will be skipped".to_string());
+      return;
+    };
+
+    let (normal_matrix, det) = normal_matrix(world);
+    let positions: Vec<Vector> = positions
+      .map(|[x, y, z]| transform_point(world, Vector::new(x.into(), y.into(), z.into())))
+      .collect();
+    let normals: Vec<Vector> = reader.read_normals().map_or(Vec::new(), |normals| {
+      normals
+        .map(|[x, y, z]| {
+          transform_vector(&normal_matrix, Vector::new(x.into(), y.into(), z.into())).unit_vector()
+        })
+        .collect()
+    });
+    // glTF puts the texture origin at the top left, the renderer at the bottom left
+    let uvs: Vec<(f64, f64)> = reader.read_tex_coords(0).map_or(Vec::new(), |uvs| {
+      uvs
+        .into_f32()
+        .map(|[u, v]| (u.into(), 1.0 - f64::from(v)))
+        .collect()
+    });
+    let indices: Vec<usize> = match reader.read_indices() {
+      Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+      None => (0..positions.len()).collect(),
+    };
+    // Mesh::new indexes the vertex data directly, so a malformed file would panic there
+    if indices.iter().any(|&i| i >= positions.len()) {
+      self.warn("primitive with out of range vertex indices will be skipped".to_string());
+      return;
+    }
+
+    let mat = self.material(&primitive.material());
+    let vertex = |i: usize| FaceVertex {
+      position: i,
+      normal: (i < normals.len()).then_some(i),
+      uv: (i < uvs.len()).then_some(i),
+    };
+    let faces = indices
+      .chunks_exact(3)
+      .map(|corners| {
+        let mut vertices = [vertex(corners[0]), vertex(corners[1]), vertex(corners[2])];
+        // A mirroring transform flips the winding, so swap it back to keep faces pointing out
+        if det < 0.0 {
+          vertices.swap(1, 2);
+        }
+        Face {
+          vertices,
+          mat: mat.clone(),
+        }
+      })
+      .collect();
+
+    let data = MeshData {
+      positions,
+      normals,
+      uvs,
+    };
+    self.world.add(sync::Arc::new(mesh::Mesh::new(data, faces)));
+  }
+
+  fn material(&mut self, m: &::gltf::Material) -> sync::Arc<dyn material::Material> {
+    if let Some(mat) = self.materials.get(&m.index()) {
+      return mat.clone();
+    }
+
+    let pbr = m.pbr_metallic_roughness();
+    if pbr.base_color_texture().is_some()
+      || pbr.metallic_roughness_texture().is_some()
+      || m.normal_texture().is_some()
+    {
+      self.warn("textures are not supported, using material factors instead".to_string());
+    }
+
+    let [r, g, b, alpha] = pbr.base_color_factor().map(f64::from);
+    let albedo = Vector::new(r, g, b);
+    let transmission = m.transmission().map_or(0.0, |t| t.transmission_factor());
+    let blended = m.alpha_mode() == ::gltf::material::AlphaMode::Blend && alpha < 1.0;
+
+    let emissive = m.emissive_factor().map(f64::from);
+    let mat: sync::Arc<dyn material::Material> = if emissive != [0.0; 3] {
+      let [r, g, b] = emissive;
+      sync::Arc::new(material::DiffuseLight {
+        colour: Vector::new(r, g, b),
+        intensity: m.emissive_strength().unwrap_or(1.0).into(),
+      })
+    } else if transmission > 0.0 || blended {
+      sync::Arc::new(material::Dialetric {
+        refraction_index: m.ior().unwrap_or(1.5).into(),
+      })
+    } else if pbr.metallic_factor() >= 0.5 {
+      sync::Arc::new(material::Metal::new(albedo, pbr.roughness_factor().into()))
+    } else {
+      sync::Arc::new(material::Lambertian::new(albedo))
+    };
+    self.materials.insert(m.index(), mat.clone());
+    mat
+  }
+
+  fn set_camera(&mut self, c: &::gltf::Camera, world: &Matrix) {
+    if self.camera.is_some() {
+      return;
+    }
+    let Projection::Perspective(perspective) = c.projection() else {
+      self.warn("orthographic cameras are not supported".to_string());
+      return;
+    };
+
+    // glTF cameras look down their local -z axis with +y up
+    let lookfrom = transform_point(world, Vector::new(0.0, 0.0, 0.0));
+    let forward = transform_vector(world, Vector::new(0.0, 0.0, -1.0));
+    let vup = transform_vector(world, Vector::new(0.0, 1.0, 0.0));
+
+    let mut camera = camera::Camera::default();
+    if let Some(aspect_ratio) = perspective.aspect_ratio() {
+      camera.set_aspect_ratio(aspect_ratio.into());
+    }
+    camera.set_view(
+      lookfrom,
+      lookfrom + forward.unit_vector(),
+      vup,
+      f64::from(perspective.yfov()).to_degrees(),
+    );
+    self.camera = Some(camera);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Binary glTF holding one triangle primitive with the given vertex indices
+  fn glb(indices: [u16; 3]) -> Vec<u8> {
+    let mut bin: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+      .iter()
+      .flat_map(|x| x.to_le_bytes())
+      .collect();
+    bin.extend(indices.iter().flat_map(|i| i.to_le_bytes()));
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let mut json = format!(
+      r#"{{
+        "asset": {{"version": "2.0"}},
+        "scene": 0,
+        "scenes": [{{"nodes": [0]}}],
+        "nodes": [{{"mesh": 0}}],
+        "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}],
+        "buffers": [{{"byteLength": {}}}],
+        "bufferViews": [
+          {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+          {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+        ],
+        "accessors": [
+          {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+            "min": [0, 0, 0], "max": [1, 1, 0]}},
+          {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+        ]
+      }}"#,
+      bin.len()
+    )
+    .into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    let mut glb = Vec::new();
+    glb.extend(b"glTF");
+    glb.extend(2u32.to_le_bytes());
+    glb.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(json);
+    glb.extend((bin.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(bin);
+    glb
+  }
+
+  fn load_glb(name: &str, indices: [u16; 3]) -> Import {
+    let path = std::env::temp_dir().join(format!("rust-ray-tracing-{name}.glb"));
+    fs::write(&path, glb(indices)).unwrap();
+    let import = load(&path);
+    fs::remove_file(&path).unwrap();
+    import.unwrap()
+  }
+
+  #[test]
+  fn loads_triangles() {
+    let import = load_glb("triangle", [0, 1, 2]);
+    assert_eq!(import.scene.world.objects.len(), 1);
+  }
+
+  #[test]
+  fn skips_primitives_with_out_of_range_indices() {
+    let import = load_glb("bad-index", [0, 1, 5]);
+    assert!(import.scene.world.objects.is_empty());
+    assert!(
+      import
+        .warnings
+        .iter()
+        .any(|w| w.contains("out of range vertex indices")),
+      "{:?}",
+      import.warnings
+    );
+  }
+}
@@ -1,20 +1,35 @@
-use crate::lib::{interval, material, ray, vector};
-use std::rc;
+use crate::lib::{aabb, interval, material, ray, vector};
+use std::sync;
 
 pub struct HitRecord {
   pub point: vector::Vector,
   pub normal: vector::Vector,
   pub t: f64,
-  pub mat: rc::Rc<dyn material::Material>,
+  pub mat: sync::Arc<dyn material::Material>,
+  // Surface coordinates of the hit point, for texturing
+  pub u: f64,
+  pub v: f64,
+  // How fast the hit point moves along the surface as u and v increase, for orienting normal
+  // maps. Zero when the surface doesn't provide them.
+  pub dpdu: vector::Vector,
+  pub dpdv: vector::Vector,
   pub front_face: bool,
 }
 
+// Shared by every empty record, since they're made for each object a ray is tested against and
+// shouldn't allocate
+static PLACEHOLDER_MATERIAL: sync::OnceLock<sync::Arc<dyn material::Material>> =
+  sync::OnceLock::new();
+
 impl HitRecord {
+  #[allow(dead_code)]
   pub fn new(
     point: vector::Vector,
     normal: vector::Vector,
-    mat: rc::Rc<dyn material::Material>,
+    mat: sync::Arc<dyn material::Material>,
     t: f64,
+    u: f64,
+    v: f64,
     front_face: bool,
   ) -> HitRecord {
     HitRecord {
@@ -22,6 +37,10 @@ impl HitRecord {
       normal,
       t,
       mat,
+      u,
+      v,
+      dpdu: vector::Vector::new(0.0, 0.0, 0.0),
+      dpdv: vector::Vector::new(0.0, 0.0, 0.0),
       front_face,
     }
   }
@@ -29,10 +48,18 @@ impl HitRecord {
     Self {
       point: vector::Vector::new(0.0, 0.0, 0.0),
       normal: vector::Vector::new(0.0, 0.0, 0.0),
-      mat: rc::Rc::new(material::Lambertian {
-        albedo: vector::Vector::new(0.0, 0.0, 0.0),
-      }), // Placeholder, will be overwritten
+      mat: PLACEHOLDER_MATERIAL
+        .get_or_init(|| {
+          sync::Arc::new(material::Lambertian::new(vector::Vector::new(
+            0.0, 0.0, 0.0,
+          )))
+        })
+        .clone(), // Placeholder, will be overwritten
       t: 0.0,
+      u: 0.0,
+      v: 0.0,
+      dpdu: vector::Vector::new(0.0, 0.0, 0.0),
+      dpdv: vector::Vector::new(0.0, 0.0, 0.0),
       front_face: true,
     }
   }
@@ -45,6 +72,44 @@ impl HitRecord {
   }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
   fn hit(&self, r: &ray::Ray, ray_t: interval::Interval, hit_rec: &mut HitRecord) -> bool;
+
+  fn bounding_box(&self) -> aabb::Aabb;
+
+  // Probability density, per unit solid angle, of random(origin) picking direction. 0 for
+  // objects that can't be sampled as lights, or when the direction misses the object.
+  fn pdf_value(&self, _origin: &vector::Vector, _direction: &vector::Vector) -> f64 {
+    0.0
+  }
+
+  // Direction from origin towards a random point on the object, for sampling it as a light
+  fn random(&self, _origin: &vector::Vector) -> vector::Vector {
+    vector::Vector::new(1.0, 0.0, 0.0)
+  }
+}
+
+// pdf_value of a flat light whose points are picked uniformly over its area, for a direction
+// that hits it at t (in units of direction's length) where its normal is normal. The density
+// per unit area, 1 / area, becomes distance^2 / (cos * area) per unit solid angle.
+pub fn area_light_pdf(
+  direction: &vector::Vector,
+  t: f64,
+  normal: &vector::Vector,
+  area: f64,
+) -> f64 {
+  let distance_squared = t * t * direction.length_squared();
+  let cosine = (direction.dot(normal) / direction.length()).abs();
+  distance_squared / (cosine * area)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn empty_records_share_their_placeholder_material() {
+    let (a, b) = (HitRecord::new_empty(), HitRecord::new_empty());
+    assert!(sync::Arc::ptr_eq(&a.mat, &b.mat));
+  }
 }
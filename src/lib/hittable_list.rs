@@ -1,33 +1,28 @@
-use crate::lib::{hittable, interval, material, ray, vector};
-use std::rc;
+use crate::lib::{aabb, hittable, interval, ray, utility, vector};
+use std::sync;
 
 pub struct HittableList {
-  pub objects: Vec<rc::Rc<dyn hittable::Hittable>>,
+  pub objects: Vec<sync::Arc<dyn hittable::Hittable>>,
+  bbox: aabb::Aabb,
 }
 
 impl HittableList {
   pub fn new_empty() -> HittableList {
     HittableList {
       objects: Vec::new(),
+      bbox: aabb::EMPTY,
     }
   }
 
-  pub fn new(&mut self, object: rc::Rc<dyn hittable::Hittable>) -> HittableList {
-    let mut h = HittableList {
-      objects: Vec::new(),
-    };
-
-    h.objects.push(object);
-
-    return h;
-  }
-
-  pub fn add(&mut self, object: rc::Rc<dyn hittable::Hittable>) {
+  pub fn add(&mut self, object: sync::Arc<dyn hittable::Hittable>) {
+    self.bbox = aabb::Aabb::enclosing(&self.bbox, &object.bounding_box());
     self.objects.push(object);
   }
 
+  #[allow(dead_code)]
   pub fn clear(&mut self) {
     self.objects.clear();
+    self.bbox = aabb::EMPTY;
   }
 }
 
@@ -55,4 +50,30 @@ impl hittable::Hittable for HittableList {
     }
     hit_anything
   }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+
+  // Each object is equally likely to be picked
+  fn pdf_value(&self, origin: &vector::Vector, direction: &vector::Vector) -> f64 {
+    if self.objects.is_empty() {
+      return 0.0;
+    }
+    let sum: f64 = self
+      .objects
+      .iter()
+      .map(|object| object.pdf_value(origin, direction))
+      .sum();
+    sum / self.objects.len() as f64
+  }
+
+  fn random(&self, origin: &vector::Vector) -> vector::Vector {
+    if self.objects.is_empty() {
+      return vector::Vector::new(1.0, 0.0, 0.0);
+    }
+    let i =
+      ((utility::random_df() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+    self.objects[i].random(origin)
+  }
 }
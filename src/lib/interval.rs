@@ -1,11 +1,13 @@
 use crate::lib::utility;
 
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Interval {
   pub min: f64,
   pub max: f64,
 }
 
 impl Interval {
+  #[allow(dead_code)]
   pub fn new_empty() -> Interval {
     Interval {
       min: -utility::INFINITY,
@@ -17,6 +19,19 @@ impl Interval {
     Interval { min, max }
   }
 
+  // Smallest interval containing both a and b
+  pub fn enclosing(a: &Interval, b: &Interval) -> Interval {
+    Interval {
+      min: a.min.min(b.min),
+      max: a.max.max(b.max),
+    }
+  }
+
+  // True for intervals with nothing in them, like EMPTY
+  pub fn is_empty(&self) -> bool {
+    self.min > self.max
+  }
+
   pub fn size(&self) -> f64 {
     self.max - self.min
   }
@@ -37,6 +52,11 @@ impl Interval {
     }
     x
   }
+
+  pub fn expand(&self, delta: f64) -> Interval {
+    let padding = delta / 2.0;
+    Interval::new(self.min - padding, self.max + padding)
+  }
 }
 
 pub const EMPTY: Interval = Interval::new(utility::INFINITY, -utility::INFINITY);
@@ -1,73 +1,189 @@
-use crate::lib::{hittable, ray, utility, vector};
+use crate::lib::{hittable, normal_map, ray, texture, utility, vector};
+use std::sync;
 
-pub trait Material {
-  fn scatter(
+// A direction picked by Material::sample for light to arrive from, towards r_in's origin
+pub struct Sample {
+  // Unit length
+  pub direction: vector::Vector,
+  // What the light arriving along direction is multiplied by: eval / pdf, or for a delta lobe
+  // the fraction of light it passes on
+  pub attenuation: vector::Vector,
+  // Probability density of picking direction per unit solid angle, 0 for a delta lobe
+  pub pdf: f64,
+  // Whether direction came from a perfectly specular lobe, like a mirror or glass, which no
+  // other direction can reach. Light sampling can't find these, so they're never weighted
+  // against it.
+  pub delta: bool,
+}
+
+// How a surface scatters light. The BSDF is described by eval and pdf for use with light
+// sampling, and sample picks directions from it in proportion to pdf for continuing paths.
+// Delta lobes are invisible to eval and pdf, and only ever show up through sample.
+pub trait Material: Send + Sync {
+  // Picks a direction to continue the path in, or None if the light is absorbed
+  fn sample(&self, r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample>;
+
+  // The BSDF times the cosine between direction and the normal, the fraction of light arriving
+  // from direction (which need not be unit length) sent back along r_in
+  fn eval(
     &self,
-    r_in: &ray::Ray,
-    rec: &hittable::HitRecord,
-    attenuation: &mut vector::Vector,
-    scattered: &mut ray::Ray,
-  ) -> bool;
+    _r_in: &ray::Ray,
+    _rec: &hittable::HitRecord,
+    _direction: &vector::Vector,
+  ) -> vector::Vector {
+    vector::Vector::new(0.0, 0.0, 0.0)
+  }
+
+  // Probability density, per unit solid angle, of sample choosing direction
+  fn pdf(&self, _r_in: &ray::Ray, _rec: &hittable::HitRecord, _direction: &vector::Vector) -> f64 {
+    0.0
+  }
+
+  // Radiance given off at the hit point towards the incoming ray, black for anything that
+  // isn't a light
+  fn emitted(&self, _r_in: &ray::Ray, _rec: &hittable::HitRecord) -> vector::Vector {
+    vector::Vector::new(0.0, 0.0, 0.0)
+  }
+
+  // Normal to shade the hit with in place of rec.normal, on the same side of the surface.
+  // The camera swaps it in before calling anything else on the material.
+  fn shading_normal(&self, rec: &hittable::HitRecord) -> vector::Vector {
+    rec.normal
+  }
 }
 
 pub struct Lambertian {
-  pub albedo: vector::Vector,
+  pub albedo: sync::Arc<dyn texture::Texture>,
+}
+
+impl Lambertian {
+  // The same albedo all over
+  pub fn new(albedo: vector::Vector) -> Lambertian {
+    Lambertian {
+      albedo: sync::Arc::new(texture::Solid { colour: albedo }),
+    }
+  }
 }
 
 impl Material for Lambertian {
-  fn scatter(
+  // Offsetting the normal by a random unit vector picks directions with density cos(theta) / pi
+  fn sample(&self, _r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample> {
+    let mut direction = rec.normal + vector::Vector::random_unit_vector();
+    if direction.near_zero() {
+      direction = rec.normal;
+    }
+
+    let direction = direction.unit_vector();
+    Some(Sample {
+      direction,
+      attenuation: self.albedo.value(rec.u, rec.v, &rec.point),
+      pdf: rec.normal.dot(&direction).max(0.0) / utility::PI,
+      delta: false,
+    })
+  }
+
+  fn eval(
     &self,
-    _r_in: &ray::Ray,
+    r_in: &ray::Ray,
     rec: &hittable::HitRecord,
-    attenuation: &mut vector::Vector,
-    scattered: &mut ray::Ray,
-  ) -> bool {
-    let mut scatter_direction = rec.normal + vector::Vector::random_unit_vector();
-
-    if scatter_direction.near_zero() {
-      scatter_direction = rec.normal;
-    }
+    direction: &vector::Vector,
+  ) -> vector::Vector {
+    self.albedo.value(rec.u, rec.v, &rec.point) * self.pdf(r_in, rec, direction)
+  }
 
-    *scattered = ray::Ray::new(rec.point, scatter_direction);
-    *attenuation = self.albedo;
-    return true;
+  fn pdf(&self, _r_in: &ray::Ray, rec: &hittable::HitRecord, direction: &vector::Vector) -> f64 {
+    rec.normal.dot(&direction.unit_vector()).max(0.0) / utility::PI
   }
 }
 
+// Reflects mirror-like, blurred by offsetting the reflection by a random vector of length fuzz.
+// Offsets that would take the ray below the surface absorb it.
 pub struct Metal {
-  pub albedo: vector::Vector,
+  pub albedo: sync::Arc<dyn texture::Texture>,
   pub fuzz: f64,
 }
 
+impl Metal {
+  // The same albedo all over
+  pub fn new(albedo: vector::Vector, fuzz: f64) -> Metal {
+    Metal {
+      albedo: sync::Arc::new(texture::Solid { colour: albedo }),
+      fuzz,
+    }
+  }
+
+  fn reflected(r_in: &ray::Ray, rec: &hittable::HitRecord) -> vector::Vector {
+    r_in.dir.unit_vector().reflect(rec.normal)
+  }
+}
+
 impl Material for Metal {
-  fn scatter(
+  fn sample(&self, r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample> {
+    let reflected = Metal::reflected(r_in, rec);
+    if self.fuzz <= 0.0 {
+      return Some(Sample {
+        direction: reflected,
+        attenuation: self.albedo.value(rec.u, rec.v, &rec.point),
+        pdf: 0.0,
+        delta: true,
+      });
+    }
+
+    let direction = reflected + (vector::Vector::random_unit_vector() * self.fuzz);
+    if direction.dot(&rec.normal) <= 0.0 || direction.length_squared() == 0.0 {
+      return None;
+    }
+    let direction = direction.unit_vector();
+    Some(Sample {
+      direction,
+      attenuation: self.albedo.value(rec.u, rec.v, &rec.point),
+      pdf: self.pdf(r_in, rec, &direction),
+      delta: false,
+    })
+  }
+
+  // Everything sample doesn't absorb is reflected, so this is just albedo * pdf
+  fn eval(
     &self,
     r_in: &ray::Ray,
     rec: &hittable::HitRecord,
-    attenuation: &mut vector::Vector,
-    scattered: &mut ray::Ray,
-  ) -> bool {
-    let reflected =
-      r_in.dir.reflect(rec.normal) + (vector::Vector::random_unit_vector() * self.fuzz);
-    *scattered = ray::Ray::new(rec.point, reflected);
-    *attenuation = self.albedo;
-    return scattered.dir.dot(&rec.normal) > 0.0;
+    direction: &vector::Vector,
+  ) -> vector::Vector {
+    self.albedo.value(rec.u, rec.v, &rec.point) * self.pdf(r_in, rec, direction)
+  }
+
+  // The offset reflections cover a sphere of radius fuzz around the mirror direction, evenly by
+  // area. A direction w crosses that sphere where t * w lies on it, and each crossing at
+  // distance t contributes t^2 / (4 pi fuzz^2 |cos|), cos being between w and the sphere's
+  // normal there. Directions below the surface are absorbed, so have no density.
+  fn pdf(&self, r_in: &ray::Ray, rec: &hittable::HitRecord, direction: &vector::Vector) -> f64 {
+    if self.fuzz <= 0.0 || direction.dot(&rec.normal) <= 0.0 {
+      return 0.0;
+    }
+    let cos_alpha = Metal::reflected(r_in, rec).dot(&direction.unit_vector());
+    let fuzz_squared = self.fuzz * self.fuzz;
+    let half_chord_squared = fuzz_squared - (1.0 - cos_alpha * cos_alpha);
+    if half_chord_squared <= 0.0 {
+      return 0.0;
+    }
+
+    let half_chord = half_chord_squared.sqrt();
+    [cos_alpha - half_chord, cos_alpha + half_chord]
+      .iter()
+      .filter(|&&t| t > 0.0)
+      .map(|t| t * t / (4.0 * utility::PI * self.fuzz * half_chord))
+      .sum()
   }
 }
 
+// Glass and water: reflects or refracts along a single direction, picked in proportion to the
+// Fresnel reflectance
 pub struct Dialetric {
   pub refraction_index: f64,
 }
 
 impl Material for Dialetric {
-  fn scatter(
-    &self,
-    r_in: &ray::Ray,
-    rec: &hittable::HitRecord,
-    attenuation: &mut vector::Vector,
-    scattered: &mut ray::Ray,
-  ) -> bool {
-    *attenuation = vector::Vector::new(1.0, 1.0, 1.0);
+  fn sample(&self, r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample> {
     let ri = if rec.front_face {
       1.0 / self.refraction_index
     } else {
@@ -86,8 +202,12 @@ impl Material for Dialetric {
         unit_direction.refract(rec.normal, ri)
       };
 
-    *scattered = ray::Ray::new(rec.point, direction);
-    return true;
+    Some(Sample {
+      direction: direction.unit_vector(),
+      attenuation: vector::Vector::new(1.0, 1.0, 1.0),
+      pdf: 0.0,
+      delta: true,
+    })
   }
 }
 
@@ -98,3 +218,295 @@ impl Dialetric {
     r0 + ((1.0 - r0) * ((1.0 - cosine).powi(5)))
   }
 }
+
+// Emits colour * intensity from both sides of a surface and doesn't reflect anything
+pub struct DiffuseLight {
+  pub colour: vector::Vector,
+  pub intensity: f64,
+}
+
+impl Material for DiffuseLight {
+  fn sample(&self, _r_in: &ray::Ray, _rec: &hittable::HitRecord) -> Option<Sample> {
+    None
+  }
+
+  fn emitted(&self, _r_in: &ray::Ray, _rec: &hittable::HitRecord) -> vector::Vector {
+    self.colour * self.intensity
+  }
+}
+
+// Scatters light evenly in every direction, for use inside a medium::ConstantMedium. Phase
+// functions like this have no surface to take a cosine against, so eval is just albedo times
+// the phase function.
+pub struct Isotropic {
+  pub albedo: sync::Arc<dyn texture::Texture>,
+}
+
+impl Material for Isotropic {
+  fn sample(&self, _r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample> {
+    Some(Sample {
+      direction: vector::Vector::random_unit_vector(),
+      attenuation: self.albedo.value(rec.u, rec.v, &rec.point),
+      pdf: 1.0 / (4.0 * utility::PI),
+      delta: false,
+    })
+  }
+
+  fn eval(
+    &self,
+    r_in: &ray::Ray,
+    rec: &hittable::HitRecord,
+    direction: &vector::Vector,
+  ) -> vector::Vector {
+    self.albedo.value(rec.u, rec.v, &rec.point) * self.pdf(r_in, rec, direction)
+  }
+
+  fn pdf(&self, _r_in: &ray::Ray, _rec: &hittable::HitRecord, _direction: &vector::Vector) -> f64 {
+    1.0 / (4.0 * utility::PI)
+  }
+}
+
+// Henyey-Greenstein phase function, for media that scatter mostly forwards or backwards like
+// haze and smoke. g is the average cosine between the incoming and scattered directions,
+// between -1 (all straight back) and 1 (all straight on), with 0 the same as Isotropic.
+pub struct HenyeyGreenstein {
+  pub albedo: sync::Arc<dyn texture::Texture>,
+  pub g: f64,
+}
+
+impl HenyeyGreenstein {
+  fn phase(&self, cos_theta: f64) -> f64 {
+    let g = self.g;
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * utility::PI * denom * denom.sqrt())
+  }
+}
+
+impl Material for HenyeyGreenstein {
+  // Inverts the cumulative distribution of the angle from the incoming direction, then turns
+  // about it uniformly
+  fn sample(&self, r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample> {
+    let g = self.g;
+    let xi = utility::random_df();
+    let cos_theta = if g.abs() < 1e-3 {
+      1.0 - 2.0 * xi
+    } else {
+      let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+      ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * utility::PI * utility::random_df();
+
+    let forward = r_in.dir.unit_vector();
+    let (tangent, bitangent) = forward.onb();
+    let direction = (forward * cos_theta)
+      + (tangent * (sin_theta * phi.cos()))
+      + (bitangent * (sin_theta * phi.sin()));
+
+    Some(Sample {
+      direction: direction.unit_vector(),
+      attenuation: self.albedo.value(rec.u, rec.v, &rec.point),
+      pdf: self.phase(cos_theta),
+      delta: false,
+    })
+  }
+
+  fn eval(
+    &self,
+    r_in: &ray::Ray,
+    rec: &hittable::HitRecord,
+    direction: &vector::Vector,
+  ) -> vector::Vector {
+    self.albedo.value(rec.u, rec.v, &rec.point) * self.pdf(r_in, rec, direction)
+  }
+
+  // Sampling follows the phase function exactly
+  fn pdf(&self, r_in: &ray::Ray, _rec: &hittable::HitRecord, direction: &vector::Vector) -> f64 {
+    self.phase(r_in.dir.unit_vector().dot(&direction.unit_vector()))
+  }
+}
+
+// Another material with its shading normal bent by a normal or bump map, for surface detail
+// like brickwork or brushed metal that would be too fine to model
+pub struct NormalMapped {
+  pub base: sync::Arc<dyn Material>,
+  pub map: normal_map::NormalMap,
+}
+
+impl Material for NormalMapped {
+  fn sample(&self, r_in: &ray::Ray, rec: &hittable::HitRecord) -> Option<Sample> {
+    self.base.sample(r_in, rec)
+  }
+
+  fn eval(
+    &self,
+    r_in: &ray::Ray,
+    rec: &hittable::HitRecord,
+    direction: &vector::Vector,
+  ) -> vector::Vector {
+    self.base.eval(r_in, rec, direction)
+  }
+
+  fn pdf(&self, r_in: &ray::Ray, rec: &hittable::HitRecord, direction: &vector::Vector) -> f64 {
+    self.base.pdf(r_in, rec, direction)
+  }
+
+  fn emitted(&self, r_in: &ray::Ray, rec: &hittable::HitRecord) -> vector::Vector {
+    self.base.emitted(r_in, rec)
+  }
+
+  fn shading_normal(&self, rec: &hittable::HitRecord) -> vector::Vector {
+    self.map.perturb(rec)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLES: usize = 200_000;
+  // Histogram bins, evenly spaced in cos(theta) and phi about the normal, so each covers the same
+  // solid angle
+  const COS_BINS: usize = 8;
+  const PHI_BINS: usize = 8;
+
+  // Hit on the z = 0 plane facing +z, by a ray coming down at 45 degrees
+  fn incoming() -> (ray::Ray, hittable::HitRecord) {
+    let r_in = ray::Ray::new(
+      vector::Vector::new(-1.0, 0.0, 1.0),
+      vector::Vector::new(1.0, 0.0, -1.0),
+    );
+    let mut rec = hittable::HitRecord::new_empty();
+    rec.normal = vector::Vector::new(0.0, 0.0, 1.0);
+    (r_in, rec)
+  }
+
+  fn bin_of(direction: &vector::Vector) -> usize {
+    let cos_bin = ((direction.z + 1.0) / 2.0 * COS_BINS as f64) as usize;
+    let phi = direction.y.atan2(direction.x) + utility::PI;
+    let phi_bin = (phi / (2.0 * utility::PI) * PHI_BINS as f64) as usize;
+    cos_bin.min(COS_BINS - 1) * PHI_BINS + phi_bin.min(PHI_BINS - 1)
+  }
+
+  // Integral of the material's pdf over each bin, by the midpoint rule on a fine grid
+  fn expected_histogram(
+    mat: &dyn Material,
+    r_in: &ray::Ray,
+    rec: &hittable::HitRecord,
+  ) -> Vec<f64> {
+    const STEPS: usize = 64;
+    let d_cos = 2.0 / (COS_BINS * STEPS) as f64;
+    let d_phi = 2.0 * utility::PI / (PHI_BINS * STEPS) as f64;
+
+    let mut expected = vec![0.0; COS_BINS * PHI_BINS];
+    for i in 0..COS_BINS * STEPS {
+      let cos_theta = -1.0 + (i as f64 + 0.5) * d_cos;
+      let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+      for j in 0..PHI_BINS * STEPS {
+        let phi = -utility::PI + (j as f64 + 0.5) * d_phi;
+        let direction =
+          vector::Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        expected[bin_of(&direction)] += mat.pdf(r_in, rec, &direction) * d_cos * d_phi;
+      }
+    }
+    expected
+  }
+
+  // Checks that sample picks directions in proportion to pdf, counting absorbed samples against
+  // the total, and that every sample agrees with eval and pdf
+  fn check_sampling(mat: &dyn Material, seed: u64) {
+    let (r_in, rec) = incoming();
+    let mut histogram = vec![0.0; COS_BINS * PHI_BINS];
+    utility::with_seed(seed, || {
+      for _ in 0..SAMPLES {
+        let Some(sample) = mat.sample(&r_in, &rec) else {
+          continue;
+        };
+        assert!(!sample.delta);
+        assert!((sample.direction.length() - 1.0).abs() < 1e-9);
+        let pdf = mat.pdf(&r_in, &rec, &sample.direction);
+        assert!((sample.pdf - pdf).abs() < 1e-9 * pdf.max(1.0));
+        let eval = mat.eval(&r_in, &rec, &sample.direction);
+        assert!((sample.attenuation - eval / pdf).length() < 1e-9);
+        histogram[bin_of(&sample.direction)] += 1.0 / SAMPLES as f64;
+      }
+    });
+
+    let expected = expected_histogram(mat, &r_in, &rec);
+    for (bin, (&observed, &expected)) in histogram.iter().zip(&expected).enumerate() {
+      assert!(
+        (observed - expected).abs() < 0.003,
+        "bin {bin}: sampled {observed}, pdf integrates to {expected}"
+      );
+    }
+  }
+
+  #[test]
+  fn lambertian_samples_match_pdf() {
+    let mat = Lambertian::new(vector::Vector::new(0.8, 0.5, 0.2));
+    check_sampling(&mat, 1);
+    let (r_in, rec) = incoming();
+    let below = vector::Vector::new(0.0, 0.0, -1.0);
+    assert_eq!(mat.pdf(&r_in, &rec, &below), 0.0);
+  }
+
+  #[test]
+  fn metal_samples_match_pdf() {
+    // Fuzz large enough that some offsets go below the surface and are absorbed
+    let mat = Metal::new(vector::Vector::new(0.8, 0.5, 0.2), 0.8);
+    check_sampling(&mat, 2);
+
+    // Without fuzz it's a perfect mirror, which only sample can see
+    let (r_in, rec) = incoming();
+    let mirror = Metal::new(vector::Vector::new(0.8, 0.5, 0.2), 0.0);
+    let sample = mirror.sample(&r_in, &rec).unwrap();
+    assert!(sample.delta);
+    assert!((sample.direction - vector::Vector::new(1.0, 0.0, 1.0).unit_vector()).length() < 1e-9);
+    assert_eq!(sample.attenuation, vector::Vector::new(0.8, 0.5, 0.2));
+    assert_eq!(mirror.pdf(&r_in, &rec, &sample.direction), 0.0);
+  }
+
+  #[test]
+  fn dialetric_splits_by_reflectance() {
+    let mat = Dialetric {
+      refraction_index: 1.5,
+    };
+    let (r_in, rec) = incoming();
+    let reflected = vector::Vector::new(1.0, 0.0, 1.0).unit_vector();
+    let refracted = r_in.dir.unit_vector().refract(rec.normal, 1.0 / 1.5);
+
+    let mut reflections = 0;
+    utility::with_seed(3, || {
+      for _ in 0..SAMPLES {
+        let sample = mat.sample(&r_in, &rec).unwrap();
+        assert!(sample.delta);
+        assert_eq!(sample.attenuation, vector::Vector::new(1.0, 1.0, 1.0));
+        assert_eq!(mat.pdf(&r_in, &rec, &sample.direction), 0.0);
+        assert_eq!(
+          mat.eval(&r_in, &rec, &sample.direction),
+          vector::Vector::new(0.0, 0.0, 0.0)
+        );
+        if (sample.direction - reflected).length() < 1e-9 {
+          reflections += 1;
+        } else {
+          assert!((sample.direction - refracted.unit_vector()).length() < 1e-9);
+        }
+      }
+    });
+
+    let fraction = reflections as f64 / SAMPLES as f64;
+    let reflectance = Dialetric::reflectance(0.5f64.sqrt(), 1.0 / 1.5);
+    assert!(
+      (fraction - reflectance).abs() < 0.003,
+      "reflected {fraction}, expected {reflectance}"
+    );
+
+    // Past the critical angle on the way out, everything is reflected
+    let (r_in, mut inside) = incoming();
+    inside.front_face = false;
+    for _ in 0..100 {
+      let sample = mat.sample(&r_in, &inside).unwrap();
+      assert!((sample.direction - reflected).length() < 1e-9);
+    }
+  }
+}
@@ -0,0 +1,78 @@
+use crate::lib::interval::{self, Interval};
+use crate::lib::ray::Ray;
+use crate::lib::vector::Vector;
+use crate::lib::{aabb, hittable, material, utility};
+use std::sync;
+
+// Fog, smoke or anything else of even density filling the inside of boundary, which must be a
+// closed shape such as a sphere or box. Rays travelling through it are scattered at a random
+// distance, more likely the denser it is, and keep going in whichever direction the phase
+// function (material::Isotropic or material::HenyeyGreenstein) picks. Rays that get through
+// without scattering pass it by untouched.
+pub struct ConstantMedium {
+  boundary: sync::Arc<dyn hittable::Hittable>,
+  neg_inv_density: f64,
+  phase: sync::Arc<dyn material::Material>,
+}
+
+impl ConstantMedium {
+  pub fn new(
+    boundary: sync::Arc<dyn hittable::Hittable>,
+    density: f64,
+    phase: sync::Arc<dyn material::Material>,
+  ) -> ConstantMedium {
+    ConstantMedium {
+      boundary,
+      neg_inv_density: -1.0 / density,
+      phase,
+    }
+  }
+}
+
+impl hittable::Hittable for ConstantMedium {
+  fn hit(&self, r: &Ray, ray_t: Interval, hit: &mut hittable::HitRecord) -> bool {
+    // Where the ray's line enters and leaves the boundary, even if that's behind its origin
+    let mut entry = hittable::HitRecord::new_empty();
+    if !self.boundary.hit(r, interval::UNIVERSE, &mut entry) {
+      return false;
+    }
+    let mut exit = hittable::HitRecord::new_empty();
+    if !self.boundary.hit(
+      r,
+      Interval::new(entry.t + 0.0001, utility::INFINITY),
+      &mut exit,
+    ) {
+      return false;
+    }
+
+    let t_min = entry.t.max(ray_t.min).max(0.0);
+    let t_max = exit.t.min(ray_t.max);
+    if t_min >= t_max {
+      return false;
+    }
+
+    // Free flight distance, exponentially distributed with mean 1 / density
+    let ray_length = r.dir.length();
+    let distance_inside = (t_max - t_min) * ray_length;
+    let hit_distance = self.neg_inv_density * utility::random_df().ln();
+    if hit_distance > distance_inside {
+      return false;
+    }
+
+    hit.t = t_min + hit_distance / ray_length;
+    hit.point = r.at(hit.t);
+    // A point in a volume has no surface, so these are arbitrary
+    hit.normal = Vector::new(1.0, 0.0, 0.0);
+    hit.front_face = true;
+    hit.u = 0.0;
+    hit.v = 0.0;
+    hit.dpdu = Vector::new(0.0, 0.0, 0.0);
+    hit.dpdv = Vector::new(0.0, 0.0, 0.0);
+    hit.mat = self.phase.clone();
+    true
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.boundary.bounding_box()
+  }
+}
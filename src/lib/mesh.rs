@@ -0,0 +1,101 @@
+use crate::lib::ray::Ray;
+use crate::lib::vector::Vector;
+use crate::lib::{aabb, bvh, hittable, interval, material, triangle};
+use std::sync;
+
+// Vertex attributes shared by all the faces of a mesh
+pub struct MeshData {
+  pub positions: Vec<Vector>,
+  pub normals: Vec<Vector>,
+  pub uvs: Vec<(f64, f64)>,
+}
+
+// Indices into MeshData for one corner of a face
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FaceVertex {
+  pub position: usize,
+  pub normal: Option<usize>,
+  pub uv: Option<usize>,
+}
+
+pub struct Face {
+  pub vertices: [FaceVertex; 3],
+  pub mat: sync::Arc<dyn material::Material>,
+}
+
+// Indexed triangle mesh. Faces refer to shared vertex data rather than holding copies, and
+// are kept in their own BVH.
+pub struct Mesh {
+  faces: bvh::BvhNode,
+}
+
+struct MeshFace {
+  data: sync::Arc<MeshData>,
+  face: Face,
+  bbox: aabb::Aabb,
+}
+
+impl Mesh {
+  // Panics if a face refers to a vertex attribute that isn't in data
+  pub fn new(data: MeshData, faces: Vec<Face>) -> Mesh {
+    let data = sync::Arc::new(data);
+    let faces = faces
+      .into_iter()
+      .map(|face| {
+        let [p0, p1, p2] = face.vertices.map(|v| data.positions[v.position]);
+        let bbox = aabb::Aabb::enclosing(
+          &aabb::Aabb::from_points(p0, p1),
+          &aabb::Aabb::from_points(p2, p2),
+        );
+        let face: sync::Arc<dyn hittable::Hittable> = sync::Arc::new(MeshFace {
+          data: data.clone(),
+          face,
+          bbox,
+        });
+        face
+      })
+      .collect();
+
+    Mesh {
+      faces: bvh::BvhNode::from_objects(faces),
+    }
+  }
+}
+
+impl hittable::Hittable for Mesh {
+  fn hit(&self, r: &Ray, ray_t: interval::Interval, hit: &mut hittable::HitRecord) -> bool {
+    self.faces.hit(r, ray_t, hit)
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.faces.bounding_box()
+  }
+}
+
+impl hittable::Hittable for MeshFace {
+  fn hit(&self, r: &Ray, ray_t: interval::Interval, hit: &mut hittable::HitRecord) -> bool {
+    let vertices = self.face.vertices;
+    let positions = vertices.map(|v| self.data.positions[v.position]);
+    let Some(intersection) = triangle::intersect(positions, r, ray_t) else {
+      return false;
+    };
+
+    // Only interpolate attributes that every corner of the face has
+    let normals = match vertices.map(|v| v.normal) {
+      [Some(n0), Some(n1), Some(n2)] => Some([n0, n1, n2].map(|n| self.data.normals[n])),
+      _ => None,
+    };
+    let uvs = match vertices.map(|v| v.uv) {
+      [Some(uv0), Some(uv1), Some(uv2)] => Some([uv0, uv1, uv2].map(|uv| self.data.uvs[uv])),
+      _ => None,
+    };
+
+    intersection.fill(r, normals, uvs, hit);
+    hit.mat = self.face.mat.clone();
+    true
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+}
@@ -1,10 +1,27 @@
+pub mod aabb;
+pub mod bvh;
 pub mod camera;
 pub mod colour;
+pub mod environment;
+pub mod framebuffer;
+pub mod gltf;
 pub mod hittable;
 pub mod hittable_list;
 pub mod interval;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod normal_map;
+pub mod obj;
+pub mod perlin;
+pub mod plane;
+pub mod progress;
+pub mod quad;
 pub mod ray;
+pub mod scene;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 pub mod utility;
 pub mod vector;
@@ -0,0 +1,101 @@
+use crate::lib::vector::Vector;
+use crate::lib::{colour, hittable, texture};
+use std::sync;
+
+// Step in u and v used to estimate the slope of a bump map
+const BUMP_DELTA: f64 = 1e-3;
+
+// Surface detail that bends the shading normal without moving the geometry
+pub enum NormalMap {
+  // Tangent-space normal map, as exported by most tools: each colour channel c holds 2c - 1 of
+  // the normal along dp/du, dp/dv and the surface normal. Image textures holding one should be
+  // loaded with Image::load_linear.
+  Tangent(sync::Arc<dyn texture::Texture>),
+  // Height field given by the luminance of a texture, raising the surface by strength scene
+  // units where the luminance is 1. Works with procedural textures as well as images.
+  Bump {
+    height: sync::Arc<dyn texture::Texture>,
+    strength: f64,
+  },
+}
+
+impl NormalMap {
+  // Shading normal at the hit, facing the same side as rec.normal
+  pub fn perturb(&self, rec: &hittable::HitRecord) -> Vector {
+    // Work with the normal facing out of the surface, which tangent space is defined against
+    let outward = if rec.front_face {
+      rec.normal
+    } else {
+      rec.normal * -1.0
+    };
+    let (tangent, bitangent) = frame(&outward, &rec.dpdu, &rec.dpdv);
+
+    let perturbed = match self {
+      NormalMap::Tangent(texture) => {
+        let c = texture.value(rec.u, rec.v, &rec.point);
+        (tangent * (2.0 * c.x - 1.0))
+          + (bitangent * (2.0 * c.y - 1.0))
+          + (outward * (2.0 * c.z - 1.0))
+      }
+      NormalMap::Bump { height, strength } => {
+        let usable = |d: &Vector, fallback: Vector| {
+          if d.length_squared() < 1e-20 {
+            fallback
+          } else {
+            *d
+          }
+        };
+        let dpdu = usable(&rec.dpdu, tangent);
+        let dpdv = usable(&rec.dpdv, bitangent);
+
+        let h = |du: f64, dv: f64| {
+          let point = rec.point + (dpdu * du) + (dpdv * dv);
+          colour::luminance(&height.value(rec.u + du, rec.v + dv, &point))
+        };
+        let h0 = h(0.0, 0.0);
+        let dhdu = (h(BUMP_DELTA, 0.0) - h0) / BUMP_DELTA;
+        let dhdv = (h(0.0, BUMP_DELTA) - h0) / BUMP_DELTA;
+
+        // The raised surface p + h * n moves at dp/du + dh/du * n as u increases, ignoring how
+        // n itself turns, and likewise for v
+        let along_u = dpdu + (outward * (strength * dhdu));
+        let along_v = dpdv + (outward * (strength * dhdv));
+        let normal = along_u.cross(&along_v);
+        if dpdu.cross(&dpdv).dot(&outward) < 0.0 {
+          normal * -1.0
+        } else {
+          normal
+        }
+      }
+    };
+
+    // Normals bent past the surface, or degenerate ones, fall back to the geometry
+    if perturbed.dot(&outward) <= 0.0 || !perturbed.length_squared().is_normal() {
+      return rec.normal;
+    }
+    let perturbed = perturbed.unit_vector();
+    if rec.front_face {
+      perturbed
+    } else {
+      perturbed * -1.0
+    }
+  }
+}
+
+// Unit tangent and bitangent perpendicular to the unit normal n, lined up with dpdu and dpdv
+// as far as possible. A surface without usable derivatives gets an arbitrary frame.
+fn frame(n: &Vector, dpdu: &Vector, dpdv: &Vector) -> (Vector, Vector) {
+  let tangent = *dpdu - (*n * n.dot(dpdu));
+  let (tangent, bitangent) = if tangent.length_squared() < 1e-20 {
+    n.onb()
+  } else {
+    let tangent = tangent.unit_vector();
+    (tangent, n.cross(&tangent))
+  };
+  // Mirrored texture coordinates flip the handedness of the frame
+  if bitangent.dot(dpdv) < 0.0 {
+    (tangent, bitangent * -1.0)
+  } else {
+    (tangent, bitangent)
+  }
+}
@@ -0,0 +1,517 @@
+use crate::lib::mesh::{self, Face, FaceVertex, MeshData};
+use crate::lib::{material, vector};
+use std::collections::HashMap;
+use std::str::SplitWhitespace;
+use std::{error, fmt, fs, io, path, sync};
+
+// Loads a Wavefront OBJ file, and any MTL libraries it references, as a single mesh.
+// Polygons are split into triangle fans. Faces before the first usemtl, or using a material
+// that isn't defined, get default_mat.
+//
+// MTL materials are mapped onto the closest of the renderer's materials:
+//   - ones with a non-black emissive colour Ke become DiffuseLight
+//   - transparent ones (d < 1, Tr > 0 or a refractive illum model) become Dialetric with
+//     refraction_index Ni
+//   - ones whose specular colour Ks outweighs the diffuse Kd, or with illum 3, become Metal
+//     with albedo Ks and a fuzz derived from the shininess Ns
+//   - everything else becomes Lambertian with albedo Kd
+pub fn load(
+  path: impl AsRef<path::Path>,
+  default_mat: sync::Arc<dyn material::Material>,
+) -> Result<mesh::Mesh, ObjError> {
+  let (data, faces) = parse(path.as_ref(), default_mat)?;
+  Ok(mesh::Mesh::new(data, faces))
+}
+
+// The vertex data and triangulated faces of an OBJ file, before they're built into a mesh
+fn parse(
+  path: &path::Path,
+  default_mat: sync::Arc<dyn material::Material>,
+) -> Result<(MeshData, Vec<Face>), ObjError> {
+  let src = read(path)?;
+
+  let mut data = MeshData {
+    positions: Vec::new(),
+    normals: Vec::new(),
+    uvs: Vec::new(),
+  };
+  let mut faces = Vec::new();
+  let mut materials: HashMap<String, sync::Arc<dyn material::Material>> = HashMap::new();
+  let mut current_mat = default_mat.clone();
+
+  for (i, line) in src.lines().enumerate() {
+    let mut line = Line::new(path, i + 1, line);
+    let Some(keyword) = line.next() else {
+      continue;
+    };
+
+    match keyword {
+      "v" => data.positions.push(line.vector()?),
+      "vn" => {
+        let normal = line.vector()?;
+        if normal.length_squared() == 0.0 {
+          return Err(line.error("normal must not be zero"));
+        }
+        data.normals.push(normal.unit_vector());
+      }
+      "vt" => {
+        let u = line.number()?;
+        let v = line.optional_number()?.unwrap_or(0.0);
+        data.uvs.push((u, v));
+      }
+      "f" => {
+        let fields: Vec<&str> = line.by_ref().collect();
+        let mut corners = Vec::new();
+        for corner in fields {
+          corners.push(face_vertex(corner, &data).map_err(|message| line.error(&message))?);
+        }
+        if corners.len() < 3 {
+          return Err(line.error("face needs at least 3 vertices"));
+        }
+        for i in 1..corners.len() - 1 {
+          faces.push(Face {
+            vertices: [corners[0], corners[i], corners[i + 1]],
+            mat: current_mat.clone(),
+          });
+        }
+      }
+      "usemtl" => {
+        let name = line.name()?;
+        current_mat = materials.get(name).unwrap_or(&default_mat).clone();
+      }
+      "mtllib" => {
+        let dir = path.parent().unwrap_or(path::Path::new(""));
+        for file in line.by_ref() {
+          materials.extend(load_mtl(&dir.join(file))?);
+        }
+      }
+      // Groups, objects, smoothing groups and everything else don't affect the mesh
+      _ => {}
+    }
+  }
+
+  Ok((data, faces))
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+  Io {
+    path: path::PathBuf,
+    error: io::Error,
+  },
+  // Line is 1-based
+  Parse {
+    path: path::PathBuf,
+    line: usize,
+    message: String,
+  },
+}
+
+impl fmt::Display for ObjError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ObjError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+      ObjError::Parse {
+        path,
+        line,
+        message,
+      } => write!(f, "{}:{line}: {message}", path.display()),
+    }
+  }
+}
+
+impl error::Error for ObjError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      ObjError::Io { error, .. } => Some(error),
+      ObjError::Parse { .. } => None,
+    }
+  }
+}
+
+// The whitespace separated fields of one line of an OBJ or MTL file
+struct Line<'a> {
+  path: &'a path::Path,
+  number: usize,
+  fields: SplitWhitespace<'a>,
+}
+
+impl<'a> Line<'a> {
+  fn new(path: &'a path::Path, number: usize, line: &'a str) -> Line<'a> {
+    let line = line.split('#').next().unwrap_or("");
+    Line {
+      path,
+      number,
+      fields: line.split_whitespace(),
+    }
+  }
+
+  fn error(&self, message: &str) -> ObjError {
+    ObjError::Parse {
+      path: self.path.to_path_buf(),
+      line: self.number,
+      message: message.to_string(),
+    }
+  }
+
+  fn optional_number(&mut self) -> Result<Option<f64>, ObjError> {
+    match self.fields.next() {
+      Some(field) => match field.parse::<f64>() {
+        Ok(x) if x.is_finite() => Ok(Some(x)),
+        Ok(_) => Err(self.error(&format!("`{field}` is not a finite number"))),
+        Err(_) => Err(self.error(&format!("`{field}` is not a number"))),
+      },
+      None => Ok(None),
+    }
+  }
+
+  fn number(&mut self) -> Result<f64, ObjError> {
+    self
+      .optional_number()?
+      .ok_or_else(|| self.error("expected a number"))
+  }
+
+  fn vector(&mut self) -> Result<vector::Vector, ObjError> {
+    Ok(vector::Vector::new(
+      self.number()?,
+      self.number()?,
+      self.number()?,
+    ))
+  }
+
+  fn name(&mut self) -> Result<&'a str, ObjError> {
+    self
+      .fields
+      .next()
+      .ok_or_else(|| self.error("expected a name"))
+  }
+}
+
+impl<'a> Iterator for Line<'a> {
+  type Item = &'a str;
+
+  fn next(&mut self) -> Option<&'a str> {
+    self.fields.next()
+  }
+}
+
+// Parses one corner of a face: v, v/vt, v//vn or v/vt/vn, with 1-based or negative indices
+fn face_vertex(corner: &str, data: &MeshData) -> Result<FaceVertex, String> {
+  let mut parts = corner.split('/');
+  let position = parts.next().unwrap_or("");
+  let uv = parts.next().filter(|s| !s.is_empty());
+  let normal = parts.next().filter(|s| !s.is_empty());
+
+  Ok(FaceVertex {
+    position: index(position, data.positions.len(), "vertex")?,
+    uv: uv
+      .map(|uv| index(uv, data.uvs.len(), "texture coordinate"))
+      .transpose()?,
+    normal: normal
+      .map(|n| index(n, data.normals.len(), "normal"))
+      .transpose()?,
+  })
+}
+
+fn index(field: &str, count: usize, kind: &str) -> Result<usize, String> {
+  let i: i64 = field
+    .parse()
+    .map_err(|_| format!("`{field}` is not a {kind} index"))?;
+  let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+  if i == 0 || resolved < 0 || resolved >= count as i64 {
+    return Err(format!("{kind} index {i} is out of range"));
+  }
+  Ok(resolved as usize)
+}
+
+fn read(path: &path::Path) -> Result<String, ObjError> {
+  fs::read_to_string(path).map_err(|error| ObjError::Io {
+    path: path.to_path_buf(),
+    error,
+  })
+}
+
+// The MTL parameters that affect which material is chosen
+struct MtlParams {
+  kd: vector::Vector,
+  ks: vector::Vector,
+  ke: vector::Vector,
+  ns: f64,
+  ni: f64,
+  dissolve: f64,
+  illum: u32,
+}
+
+impl Default for MtlParams {
+  fn default() -> Self {
+    MtlParams {
+      kd: vector::Vector::new(0.8, 0.8, 0.8),
+      ks: vector::Vector::new(0.0, 0.0, 0.0),
+      ke: vector::Vector::new(0.0, 0.0, 0.0),
+      ns: 0.0,
+      ni: 1.5,
+      dissolve: 1.0,
+      illum: 2,
+    }
+  }
+}
+
+impl MtlParams {
+  fn to_material(&self) -> sync::Arc<dyn material::Material> {
+    let max = |v: vector::Vector| v.x.max(v.y).max(v.z);
+
+    if max(self.ke) > 0.0 {
+      sync::Arc::new(material::DiffuseLight {
+        colour: self.ke,
+        intensity: 1.0,
+      })
+    } else if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+      sync::Arc::new(material::Dialetric {
+        refraction_index: self.ni,
+      })
+    } else if self.illum == 3 || max(self.ks) > max(self.kd) {
+      // Phong exponent to roughness, as in Walter et al. 2007
+      let fuzz = (2.0 / (self.ns + 2.0)).sqrt().min(1.0);
+      sync::Arc::new(material::Metal::new(self.ks, fuzz))
+    } else {
+      sync::Arc::new(material::Lambertian::new(self.kd))
+    }
+  }
+}
+
+fn load_mtl(
+  path: &path::Path,
+) -> Result<HashMap<String, sync::Arc<dyn material::Material>>, ObjError> {
+  let src = read(path)?;
+  let mut materials = HashMap::new();
+  let mut current: Option<(String, MtlParams)> = None;
+
+  for (i, line) in src.lines().enumerate() {
+    let mut line = Line::new(path, i + 1, line);
+    let Some(keyword) = line.next() else {
+      continue;
+    };
+
+    if keyword == "newmtl" {
+      let name = line.name()?.to_string();
+      if let Some((name, params)) = current.replace((name, MtlParams::default())) {
+        materials.insert(name, params.to_material());
+      }
+      continue;
+    }
+
+    let Some((_, params)) = current.as_mut() else {
+      return Err(line.error(&format!("`{keyword}` before any newmtl")));
+    };
+    match keyword {
+      "Kd" => params.kd = line.vector()?,
+      "Ks" => params.ks = line.vector()?,
+      "Ke" => params.ke = line.vector()?,
+      "Ns" => params.ns = line.number()?,
+      "Ni" => {
+        params.ni = line.number()?;
+        if params.ni <= 0.0 {
+          return Err(line.error("Ni must be greater than 0"));
+        }
+      }
+      "d" => params.dissolve = line.number()?,
+      "Tr" => params.dissolve = 1.0 - line.number()?,
+      "illum" => {
+        let illum = line.number()?;
+        if illum < 0.0 || illum.fract() != 0.0 {
+          return Err(line.error("illum must be a whole number"));
+        }
+        params.illum = illum as u32;
+      }
+      // Texture maps, ambient colour and so on aren't supported
+      _ => {}
+    }
+  }
+
+  if let Some((name, params)) = current {
+    materials.insert(name, params.to_material());
+  }
+  Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lib::{hittable, ray, utility};
+
+  fn default_mat() -> sync::Arc<dyn material::Material> {
+    sync::Arc::new(material::Lambertian::new(vector::Vector::new(
+      0.5, 0.5, 0.5,
+    )))
+  }
+
+  // Writes files into a fresh temporary directory and parses the first one as an OBJ file
+  fn parse_files(
+    name: &str,
+    files: &[(&str, &str)],
+    default_mat: sync::Arc<dyn material::Material>,
+  ) -> Result<(MeshData, Vec<Face>), ObjError> {
+    let dir = std::env::temp_dir().join(format!("rust-ray-tracing-obj-{name}"));
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+      fs::write(dir.join(file), contents).unwrap();
+    }
+    let parsed = parse(&dir.join(files[0].0), default_mat);
+    fs::remove_dir_all(&dir).unwrap();
+    parsed
+  }
+
+  fn faces(name: &str, obj: &str) -> Vec<Face> {
+    match parse_files(name, &[("mesh.obj", obj)], default_mat()) {
+      Ok((_, faces)) => faces,
+      Err(e) => panic!("{e}"),
+    }
+  }
+
+  fn error(name: &str, obj: &str) -> String {
+    match parse_files(name, &[("mesh.obj", obj)], default_mat()) {
+      Ok(_) => panic!("{name} parsed"),
+      Err(e) => e.to_string(),
+    }
+  }
+
+  fn positions(face: &Face) -> [usize; 3] {
+    face.vertices.map(|v| v.position)
+  }
+
+  const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+  #[test]
+  fn triangulates_polygons_into_fans() {
+    let quad = faces("quad", &format!("{SQUARE}f 1 2 3 4\n"));
+    assert_eq!(
+      quad.iter().map(positions).collect::<Vec<_>>(),
+      [[0, 1, 2], [0, 2, 3]]
+    );
+
+    let pentagon = faces("pentagon", &format!("{SQUARE}v 0.5 2 0\nf 1 2 3 5 4\n"));
+    assert_eq!(
+      pentagon.iter().map(positions).collect::<Vec<_>>(),
+      [[0, 1, 2], [0, 2, 4], [0, 4, 3]]
+    );
+  }
+
+  #[test]
+  fn resolves_negative_indices() {
+    let faces = faces(
+      "negative",
+      &format!("{SQUARE}vt 0 0\nvt 1 1\nvn 0 0 1\nf -4/-2/-1 -3/-1/-1 -2//-1\n"),
+    );
+    assert_eq!(positions(&faces[0]), [0, 1, 2]);
+    assert_eq!(faces[0].vertices.map(|v| v.uv), [Some(0), Some(1), None]);
+    assert_eq!(faces[0].vertices.map(|v| v.normal), [Some(0); 3]);
+  }
+
+  #[test]
+  fn rejects_out_of_range_and_zero_indices() {
+    for face in ["f 1 2 5", "f 0 1 2", "f -5 1 2", "f 1/2 2/1 3/1"] {
+      let message = error("out-of-range", &format!("{SQUARE}vt 0 0\n{face}\n"));
+      assert!(message.contains(":6: "), "{face}: {message}");
+      assert!(message.contains("out of range"), "{face}: {message}");
+    }
+  }
+
+  #[test]
+  fn rejects_zero_normals_and_non_finite_numbers() {
+    let message = error("zero-normal", "vn 0 0 0\n");
+    assert!(
+      message.ends_with(":1: normal must not be zero"),
+      "{message}"
+    );
+    for line in ["v 0 nan 0", "v inf 0 0", "vt -inf"] {
+      let message = error("non-finite", &format!("\n{line}\n"));
+      assert!(message.contains(":2: "), "{line}: {message}");
+      assert!(message.contains("not a finite number"), "{line}: {message}");
+    }
+  }
+
+  #[test]
+  fn reports_errors_with_file_and_line() {
+    let parsed = parse_files(
+      "file-line",
+      &[
+        ("mesh.obj", "# cube\nmtllib cube.mtl\n"),
+        ("cube.mtl", "newmtl red\nKd 1 0\n"),
+      ],
+      default_mat(),
+    );
+    let message = parsed.err().unwrap().to_string();
+    let path = std::env::temp_dir()
+      .join("rust-ray-tracing-obj-file-line")
+      .join("cube.mtl");
+    assert_eq!(message, format!("{}:2: expected a number", path.display()));
+
+    let message = error("file-line", "v 0 0 0\nf 1 2\n");
+    let path = std::env::temp_dir()
+      .join("rust-ray-tracing-obj-file-line")
+      .join("mesh.obj");
+    assert!(
+      message.starts_with(&format!("{}:2: ", path.display())),
+      "{message}"
+    );
+  }
+
+  #[test]
+  fn maps_mtl_materials() {
+    let mtl = "newmtl lamp\nKe 4 4 2\n\
+               newmtl glass\nKd 1 1 1\nd 0.5\nNi 1.5\n\
+               newmtl steel\nKd 0.1 0.1 0.1\nKs 0.9 0.8 0.7\nNs 1000\n\
+               newmtl clay\nKd 0.2 0.4 0.6\n";
+    let obj = format!(
+      "mtllib materials.mtl\n{SQUARE}f 1 2 3\n\
+       usemtl lamp\nf 1 2 3\nusemtl glass\nf 1 2 3\nusemtl steel\nf 1 2 3\n\
+       usemtl clay\nf 1 2 3\nusemtl missing\nf 1 2 3\n"
+    );
+    let default_mat = default_mat();
+    let files = [("mesh.obj", obj.as_str()), ("materials.mtl", mtl)];
+    let (_, faces) = parse_files("mtl", &files, default_mat.clone()).unwrap();
+    let mats: Vec<_> = faces.iter().map(|f| f.mat.clone()).collect();
+
+    // Hit facing +z, by a ray coming down at 45 degrees
+    let r_in = ray::Ray::new(
+      vector::Vector::new(-1.0, 0.0, 1.0),
+      vector::Vector::new(1.0, 0.0, -1.0),
+    );
+    let mut rec = hittable::HitRecord::new_empty();
+    rec.normal = vector::Vector::new(0.0, 0.0, 1.0);
+    let black = vector::Vector::new(0.0, 0.0, 0.0);
+
+    // Faces before any usemtl, or using an unknown material, get the default
+    assert!(sync::Arc::ptr_eq(&mats[0], &default_mat));
+    assert!(sync::Arc::ptr_eq(&mats[5], &default_mat));
+
+    assert_eq!(
+      mats[1].emitted(&r_in, &rec),
+      vector::Vector::new(4.0, 4.0, 2.0)
+    );
+    assert!(mats[1].sample(&r_in, &rec).is_none());
+
+    utility::with_seed(1, || {
+      let glass = mats[2].sample(&r_in, &rec).unwrap();
+      assert!(glass.delta);
+      assert_eq!(glass.attenuation, vector::Vector::new(1.0, 1.0, 1.0));
+
+      let steel = mats[3].sample(&r_in, &rec).unwrap();
+      assert!(!steel.delta);
+      assert_eq!(steel.attenuation, vector::Vector::new(0.9, 0.8, 0.7));
+      assert!(
+        steel
+          .direction
+          .dot(&vector::Vector::new(1.0, 0.0, 1.0).unit_vector())
+          > 0.99
+      );
+
+      let clay = mats[4].sample(&r_in, &rec).unwrap();
+      assert!(!clay.delta);
+      assert_eq!(clay.attenuation, vector::Vector::new(0.2, 0.4, 0.6));
+    });
+    for mat in &mats[2..5] {
+      assert_eq!(mat.emitted(&r_in, &rec), black);
+    }
+  }
+}
@@ -0,0 +1,80 @@
+use crate::lib::utility;
+use crate::lib::vector::Vector;
+
+const POINT_COUNT: usize = 256;
+
+// Ken Perlin's gradient noise: a random unit gradient at every integer lattice point, blended
+// smoothly in between. Features are about one unit across and the pattern repeats every 256.
+pub struct Perlin {
+  gradients: Vec<Vector>,
+  perm_x: Vec<usize>,
+  perm_y: Vec<usize>,
+  perm_z: Vec<usize>,
+}
+
+impl Perlin {
+  // The same seed always gives the same noise
+  pub fn new(seed: u64) -> Perlin {
+    utility::with_seed(seed, || Perlin {
+      gradients: (0..POINT_COUNT)
+        .map(|_| Vector::random_unit_vector())
+        .collect(),
+      perm_x: Perlin::permutation(),
+      perm_y: Perlin::permutation(),
+      perm_z: Perlin::permutation(),
+    })
+  }
+
+  // Smoothly varying value at p, roughly between -1 and 1 and 0 at every lattice point
+  pub fn noise(&self, p: &Vector) -> f64 {
+    let (i, j, k) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (u, v, w) = (p.x - i, p.y - j, p.z - k);
+    let (i, j, k) = (i as i64, j as i64, k as i64);
+
+    // Hermite smoothing, so the blend has no creases along lattice lines
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (uu, vv, ww) = (smooth(u), smooth(v), smooth(w));
+
+    let wrap = |n: i64| (n & (POINT_COUNT as i64 - 1)) as usize;
+    let mut accum = 0.0;
+    for di in 0..2 {
+      for dj in 0..2 {
+        for dk in 0..2 {
+          let gradient = self.gradients
+            [self.perm_x[wrap(i + di)] ^ self.perm_y[wrap(j + dj)] ^ self.perm_z[wrap(k + dk)]];
+          let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+          let offset = Vector::new(u - fi, v - fj, w - fk);
+          accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+            * (fj * vv + (1.0 - fj) * (1.0 - vv))
+            * (fk * ww + (1.0 - fk) * (1.0 - ww))
+            * gradient.dot(&offset);
+        }
+      }
+    }
+    accum
+  }
+
+  // Fractal sum of octaves layers of noise, each twice the frequency and half the weight of the
+  // last, between 0 and about 1
+  pub fn turbulence(&self, p: &Vector, octaves: u32) -> f64 {
+    let mut accum = 0.0;
+    let mut point = *p;
+    let mut weight = 1.0;
+    for _ in 0..octaves {
+      accum += weight * self.noise(&point);
+      weight *= 0.5;
+      point = point * 2.0;
+    }
+    accum.abs()
+  }
+
+  // Random order of 0..POINT_COUNT, by Fisher-Yates shuffle
+  fn permutation() -> Vec<usize> {
+    let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+      let target = (utility::random_df() * (i + 1) as f64) as usize;
+      p.swap(i, target.min(i));
+    }
+    p
+  }
+}
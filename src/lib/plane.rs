@@ -0,0 +1,74 @@
+use crate::lib::interval::{self, Interval};
+use crate::lib::ray::Ray;
+use crate::lib::vector::Vector;
+use crate::lib::{aabb, hittable, material};
+use std::sync;
+
+// Infinite plane through point, facing along normal. Texture coordinates are distances from
+// point along two directions in the plane, so textures tile across it.
+pub struct Plane {
+  point: Vector,
+  normal: Vector,
+  // Orthonormal directions in the plane, giving u and v
+  tangent: Vector,
+  bitangent: Vector,
+  mat: sync::Arc<dyn material::Material>,
+  bbox: aabb::Aabb,
+}
+
+impl Plane {
+  pub fn new(point: Vector, normal: Vector, mat: sync::Arc<dyn material::Material>) -> Plane {
+    let normal = normal.unit_vector();
+    let (tangent, bitangent) = normal.onb();
+
+    // Unbounded, except along the normal when the plane is axis-aligned
+    let axis = |n: usize| {
+      let others = (0..3).filter(|&m| m != n).all(|m| normal.axis(m) == 0.0);
+      if others {
+        Interval::new(point.axis(n), point.axis(n))
+      } else {
+        interval::UNIVERSE
+      }
+    };
+    let bbox = aabb::Aabb::new(axis(0), axis(1), axis(2));
+
+    Plane {
+      point,
+      normal,
+      tangent,
+      bitangent,
+      mat,
+      bbox,
+    }
+  }
+}
+
+impl hittable::Hittable for Plane {
+  fn hit(&self, r: &Ray, ray_t: Interval, hit: &mut hittable::HitRecord) -> bool {
+    let denom = self.normal.dot(&r.dir);
+    // Ray parallel to the plane
+    if denom.abs() < 1e-12 {
+      return false;
+    }
+
+    let t = (self.point - r.orig).dot(&self.normal) / denom;
+    if !ray_t.surrounds(t) {
+      return false;
+    }
+
+    hit.t = t;
+    hit.point = r.at(t);
+    let offset = hit.point - self.point;
+    hit.u = offset.dot(&self.tangent);
+    hit.v = offset.dot(&self.bitangent);
+    hit.dpdu = self.tangent;
+    hit.dpdv = self.bitangent;
+    hit.mat = self.mat.clone();
+    hit.set_face_normal(r, self.normal);
+    true
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+}
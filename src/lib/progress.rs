@@ -0,0 +1,121 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Receives render progress. Called from the render threads each time a tile finishes, with
+// the number of pixels completed so far, the total number of pixels and the time since the
+// render started.
+pub trait Progress: Send + Sync {
+  fn update(&self, completed: usize, total: usize, elapsed: Duration);
+
+  // Called once after the last update
+  fn finish(&self, _total: usize, _elapsed: Duration) {}
+}
+
+// Any closure taking (completed, total, elapsed) can be used as a progress callback
+impl<F> Progress for F
+where
+  F: Fn(usize, usize, Duration) + Send + Sync,
+{
+  fn update(&self, completed: usize, total: usize, elapsed: Duration) {
+    self(completed, total, elapsed)
+  }
+}
+
+// Reports nothing, for batch jobs and logs
+pub struct Silent;
+
+impl Progress for Silent {
+  fn update(&self, _completed: usize, _total: usize, _elapsed: Duration) {}
+}
+
+// Single-line progress bar with elapsed time and ETA, redrawn in place on stderr
+pub struct TerminalBar {
+  // Last percentage drawn, so the line is only rewritten when it grows
+  drawn: Mutex<Option<usize>>,
+}
+
+const BAR_WIDTH: usize = 40;
+
+impl TerminalBar {
+  pub fn new() -> TerminalBar {
+    TerminalBar {
+      drawn: Mutex::new(None),
+    }
+  }
+
+  fn draw(completed: usize, total: usize, elapsed: Duration, eta: Option<Duration>) {
+    let fraction = if total == 0 {
+      1.0
+    } else {
+      completed as f64 / total as f64
+    };
+    let filled = (fraction * BAR_WIDTH as f64) as usize;
+    let eta = eta.map_or("--:--".to_string(), format_duration);
+
+    let mut stderr = io::stderr().lock();
+    let _ = write!(
+      stderr,
+      "\r[{}{}] {:3}% elapsed {} eta {}",
+      "=".repeat(filled),
+      " ".repeat(BAR_WIDTH - filled),
+      (fraction * 100.0) as usize,
+      format_duration(elapsed),
+      eta
+    );
+    let _ = stderr.flush();
+  }
+}
+
+impl Default for TerminalBar {
+  fn default() -> Self {
+    TerminalBar::new()
+  }
+}
+
+impl Progress for TerminalBar {
+  fn update(&self, completed: usize, total: usize, elapsed: Duration) {
+    let percent = (completed * 100).checked_div(total).unwrap_or(100);
+    let mut drawn = self.drawn.lock().unwrap();
+    // Tiles finish out of order across threads, so never step the bar backwards
+    if drawn.is_some_and(|d| percent <= d) {
+      return;
+    }
+    *drawn = Some(percent);
+
+    // Assume the remaining pixels take as long on average as the completed ones
+    let eta =
+      (completed > 0).then(|| elapsed.mul_f64((total - completed) as f64 / completed as f64));
+    TerminalBar::draw(completed, total, elapsed, eta);
+  }
+
+  fn finish(&self, total: usize, elapsed: Duration) {
+    TerminalBar::draw(total, total, elapsed, Some(Duration::ZERO));
+    eprintln!();
+    // The next render with this bar starts again from 0%
+    *self.drawn.lock().unwrap() = None;
+  }
+}
+
+fn format_duration(d: Duration) -> String {
+  let secs = d.as_secs();
+  if secs >= 3600 {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+  } else {
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn terminal_bar_starts_over_after_finishing() {
+    let bar = TerminalBar::new();
+    bar.update(90, 100, Duration::ZERO);
+    bar.finish(100, Duration::ZERO);
+    bar.update(10, 100, Duration::ZERO);
+    assert_eq!(*bar.drawn.lock().unwrap(), Some(10));
+  }
+}
@@ -0,0 +1,140 @@
+use crate::lib::ray::Ray;
+use crate::lib::vector::Vector;
+use crate::lib::{aabb, hittable, hittable_list, interval, material, utility};
+use std::sync;
+
+// Parallelogram with corners q, q + u, q + v and q + u + v. The front face is the side
+// u x v points towards. Texture coordinates run from 0 to 1 along u and v.
+pub struct Quad {
+  q: Vector,
+  u: Vector,
+  v: Vector,
+  // u x v divided by its squared length, for finding the hit point's coordinates along u and v
+  w: Vector,
+  normal: Vector,
+  mat: sync::Arc<dyn material::Material>,
+  bbox: aabb::Aabb,
+}
+
+impl Quad {
+  pub fn new(q: Vector, u: Vector, v: Vector, mat: sync::Arc<dyn material::Material>) -> Quad {
+    let n = u.cross(&v);
+    let bbox = aabb::Aabb::enclosing(
+      &aabb::Aabb::from_points(q, q + u + v),
+      &aabb::Aabb::from_points(q + u, q + v),
+    );
+    Quad {
+      q,
+      u,
+      v,
+      w: n / n.dot(&n),
+      normal: n.unit_vector(),
+      mat,
+      bbox,
+    }
+  }
+}
+
+impl hittable::Hittable for Quad {
+  fn hit(&self, r: &Ray, ray_t: interval::Interval, hit: &mut hittable::HitRecord) -> bool {
+    let denom = self.normal.dot(&r.dir);
+    // Ray parallel to the quad's plane
+    if denom.abs() < 1e-12 {
+      return false;
+    }
+
+    let t = (self.q - r.orig).dot(&self.normal) / denom;
+    if !ray_t.surrounds(t) {
+      return false;
+    }
+
+    // Coordinates of the hit point in the plane, in units of u and v
+    let point = r.at(t);
+    let p = point - self.q;
+    let alpha = self.w.dot(&p.cross(&self.v));
+    let beta = self.w.dot(&self.u.cross(&p));
+    let unit = interval::Interval::new(0.0, 1.0);
+    if !unit.contains(alpha) || !unit.contains(beta) {
+      return false;
+    }
+
+    hit.t = t;
+    hit.point = point;
+    hit.u = alpha;
+    hit.v = beta;
+    hit.dpdu = self.u;
+    hit.dpdv = self.v;
+    hit.mat = self.mat.clone();
+    hit.set_face_normal(r, self.normal);
+    true
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+
+  fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    let mut hit = hittable::HitRecord::new_empty();
+    let ray = Ray::new(*origin, *direction);
+    if !self.hit(
+      &ray,
+      interval::Interval::new(0.001, utility::INFINITY),
+      &mut hit,
+    ) {
+      return 0.0;
+    }
+
+    let area = self.u.cross(&self.v).length();
+    hittable::area_light_pdf(direction, hit.t, &self.normal, area)
+  }
+
+  fn random(&self, origin: &Vector) -> Vector {
+    let p = self.q + (self.u * utility::random_df()) + (self.v * utility::random_df());
+    p - *origin
+  }
+}
+
+// Axis-aligned box with a and b as opposite corners, made of six outward facing quads
+pub struct Cuboid {
+  sides: hittable_list::HittableList,
+}
+
+impl Cuboid {
+  pub fn new(a: Vector, b: Vector, mat: sync::Arc<dyn material::Material>) -> Cuboid {
+    let min = Vector::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Vector::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+    let dx = Vector::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vector::new(0.0, max.y - min.y, 0.0);
+    let dz = Vector::new(0.0, 0.0, max.z - min.z);
+
+    let mut sides = hittable_list::HittableList::new_empty();
+    let mut side = |q, u, v| sides.add(sync::Arc::new(Quad::new(q, u, v, mat.clone())));
+    side(Vector::new(min.x, min.y, max.z), dx, dy); // front
+    side(Vector::new(max.x, min.y, max.z), dz * -1.0, dy); // right
+    side(Vector::new(max.x, min.y, min.z), dx * -1.0, dy); // back
+    side(Vector::new(min.x, min.y, min.z), dz, dy); // left
+    side(Vector::new(min.x, max.y, max.z), dx, dz * -1.0); // top
+    side(Vector::new(min.x, min.y, min.z), dx, dz); // bottom
+
+    Cuboid { sides }
+  }
+}
+
+impl hittable::Hittable for Cuboid {
+  fn hit(&self, r: &Ray, ray_t: interval::Interval, hit: &mut hittable::HitRecord) -> bool {
+    self.sides.hit(r, ray_t, hit)
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.sides.bounding_box()
+  }
+
+  fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    self.sides.pdf_value(origin, direction)
+  }
+
+  fn random(&self, origin: &Vector) -> Vector {
+    self.sides.random(origin)
+  }
+}
@@ -3,17 +3,25 @@ use crate::lib::vector;
 pub struct Ray {
   pub orig: vector::Vector,
   pub dir: vector::Vector,
+  // Moment the ray exists at, for objects that move while the shutter is open
+  pub time: f64,
 }
 
 impl Ray {
+  // Ray at time 0
   pub fn new(orig: vector::Vector, dir: vector::Vector) -> Ray {
-    Ray { orig, dir }
+    Ray::with_time(orig, dir, 0.0)
+  }
+
+  pub fn with_time(orig: vector::Vector, dir: vector::Vector, time: f64) -> Ray {
+    Ray { orig, dir, time }
   }
 
   pub fn at(&self, t: f64) -> vector::Vector {
     self.orig + (self.dir * t)
   }
 
+  #[allow(dead_code)]
   pub fn hit_sphere(&self, center: vector::Vector, radius: f64) -> f64 {
     let oc = center - self.orig;
     let a = self.dir.length_squared();
@@ -21,9 +29,9 @@ impl Ray {
     let c = oc.length_squared() - (radius * radius);
     let discriminant = (h * h) - (a * c);
     if discriminant < 0.0 {
-      return -1.0;
+      -1.0
     } else {
-      return (h - discriminant.sqrt()) / a;
+      (h - discriminant.sqrt()) / a
     }
   }
 }
@@ -0,0 +1,1169 @@
+use crate::lib::{
+  camera, environment, hittable, hittable_list, material, medium, normal_map, obj, perlin, plane,
+  quad, sphere, texture, transform, triangle, vector,
+};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::{error, fmt, fs, io, path, sync};
+use toml::Spanned;
+
+// A camera and world loaded from a TOML scene description, e.g.
+//
+//   [camera]
+//   lookfrom = [13.0, 2.0, 3.0]
+//   vfov = 20.0
+//
+//   [environment]
+//   type = "gradient"
+//   bottom = [1.0, 1.0, 1.0]
+//   top = [0.5, 0.7, 1.0]
+//
+//   [textures.checker]
+//   type = "checker"
+//   scale = 0.5
+//   even = [0.2, 0.3, 0.1]
+//   odd = [0.9, 0.9, 0.9]
+//
+//   [materials.ground]
+//   type = "lambertian"
+//   albedo = "checker"
+//
+//   [materials.brown]
+//   type = "lambertian"
+//   albedo = [0.4, 0.2, 0.1]
+//
+//   [[objects]]
+//   type = "sphere"
+//   center = [0.0, -1000.0, 0.0]
+//   radius = 1000.0
+//   material = "ground"
+//
+// Camera fields that are left out take the same values as Camera::default, and without an
+// environment the background is the sky gradient. Files referenced by the scene, such as
+// meshes, are found relative to the scene file.
+pub struct Scene {
+  pub camera: camera::Camera,
+  pub world: hittable_list::HittableList,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+  Io(io::Error),
+  // Line is 1-based and points at the offending value or the table containing it
+  Parse { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SceneError::Io(e) => write!(f, "could not read scene: {e}"),
+      SceneError::Parse { line, message } => write!(f, "line {line}: {message}"),
+    }
+  }
+}
+
+impl error::Error for SceneError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      SceneError::Io(e) => Some(e),
+      SceneError::Parse { .. } => None,
+    }
+  }
+}
+
+impl From<io::Error> for SceneError {
+  fn from(e: io::Error) -> Self {
+    SceneError::Io(e)
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScene {
+  #[serde(default)]
+  camera: Option<Spanned<RawCamera>>,
+  #[serde(default)]
+  environment: Option<Spanned<RawEnvironment>>,
+  #[serde(default)]
+  textures: HashMap<String, Spanned<RawTexture>>,
+  #[serde(default)]
+  materials: HashMap<String, Spanned<RawMaterial>>,
+  #[serde(default)]
+  objects: Vec<Spanned<RawObject>>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawCamera {
+  aspect_ratio: f64,
+  image_width: usize,
+  samples_per_pixel: u32,
+  max_depth: u32,
+  roulette_depth: u32,
+  shutter_open: f64,
+  shutter_close: f64,
+  lookfrom: [f64; 3],
+  lookat: [f64; 3],
+  vup: [f64; 3],
+  vfov: f64,
+  defocus_angle: f64,
+  focus_dist: f64,
+}
+
+impl Default for RawCamera {
+  fn default() -> Self {
+    RawCamera {
+      aspect_ratio: 16.0 / 9.0,
+      image_width: 400,
+      samples_per_pixel: 100,
+      max_depth: 50,
+      roulette_depth: camera::DEFAULT_ROULETTE_DEPTH,
+      shutter_open: 0.0,
+      shutter_close: 1.0,
+      lookfrom: [0.0, 0.0, 0.0],
+      lookat: [0.0, 0.0, -1.0],
+      vup: [0.0, 1.0, 0.0],
+      vfov: 90.0,
+      defocus_angle: 0.0,
+      focus_dist: 10.0,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum RawEnvironment {
+  Sky,
+  Solid {
+    colour: [f64; 3],
+  },
+  Gradient {
+    bottom: [f64; 3],
+    top: [f64; 3],
+    #[serde(default = "default_up")]
+    up: [f64; 3],
+  },
+  // Equirectangular .hdr or .exr image
+  Map {
+    file: String,
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+  },
+}
+
+fn default_up() -> [f64; 3] {
+  [0.0, 1.0, 0.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum RawTexture {
+  Solid {
+    colour: [f64; 3],
+  },
+  // 3D checkerboard of cubes scale wide
+  Checker {
+    #[serde(default = "default_scale")]
+    scale: f64,
+    even: [f64; 3],
+    odd: [f64; 3],
+  },
+  // PNG or JPEG, mapped with the surface's texture coordinates. Linear images hold data, like
+  // normal maps, rather than sRGB colours.
+  Image {
+    file: String,
+    #[serde(default)]
+    wrap: RawWrap,
+    #[serde(default)]
+    linear: bool,
+  },
+  // Procedural textures built on Perlin noise, blending between the colours low and high. The
+  // same seed always gives the same pattern.
+  Noise {
+    #[serde(default = "default_scale")]
+    frequency: f64,
+    #[serde(default)]
+    low: [f64; 3],
+    #[serde(default = "default_high")]
+    high: [f64; 3],
+    #[serde(default)]
+    seed: u64,
+  },
+  Turbulence {
+    #[serde(default = "default_scale")]
+    frequency: f64,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default)]
+    low: [f64; 3],
+    #[serde(default = "default_high")]
+    high: [f64; 3],
+    #[serde(default)]
+    seed: u64,
+  },
+  Marble {
+    #[serde(default = "default_scale")]
+    frequency: f64,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default = "default_distortion")]
+    distortion: f64,
+    #[serde(default)]
+    low: [f64; 3],
+    #[serde(default = "default_high")]
+    high: [f64; 3],
+    #[serde(default)]
+    seed: u64,
+  },
+  Wood {
+    #[serde(default = "default_scale")]
+    frequency: f64,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default = "default_distortion")]
+    distortion: f64,
+    #[serde(default)]
+    low: [f64; 3],
+    #[serde(default = "default_high")]
+    high: [f64; 3],
+    #[serde(default)]
+    seed: u64,
+  },
+}
+
+fn default_scale() -> f64 {
+  1.0
+}
+
+fn default_high() -> [f64; 3] {
+  [1.0, 1.0, 1.0]
+}
+
+fn default_octaves() -> u32 {
+  7
+}
+
+fn default_distortion() -> f64 {
+  1.5
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum RawWrap {
+  #[default]
+  Repeat,
+  Clamp,
+  Mirror,
+}
+
+// A material colour, given directly or as the name of a texture
+#[derive(Deserialize)]
+#[serde(
+  untagged,
+  expecting = "expected a colour [r, g, b] or the name of a texture"
+)]
+enum RawAlbedo {
+  Colour([f64; 3]),
+  Texture(String),
+}
+
+// Read through a plain table rather than with #[serde(flatten)], which can't deny unknown
+// fields, so that a misspelt key in the kind's own fields is still an error
+#[derive(Deserialize)]
+#[serde(try_from = "toml::Table")]
+struct RawMaterial {
+  kind: RawMaterialKind,
+  // Names of textures to bend the shading normal with, at most one of the two
+  normal_map: Option<String>,
+  bump_map: Option<String>,
+  bump_strength: f64,
+}
+
+impl TryFrom<toml::Table> for RawMaterial {
+  type Error = toml::de::Error;
+
+  fn try_from(mut table: toml::Table) -> Result<Self, Self::Error> {
+    Ok(RawMaterial {
+      normal_map: take(&mut table, "normal_map")?,
+      bump_map: take(&mut table, "bump_map")?,
+      bump_strength: take(&mut table, "bump_strength")?.unwrap_or(0.01),
+      kind: RawMaterialKind::deserialize(toml::Value::Table(table))?,
+    })
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum RawMaterialKind {
+  Lambertian {
+    albedo: RawAlbedo,
+  },
+  Metal {
+    albedo: RawAlbedo,
+    #[serde(default)]
+    fuzz: f64,
+  },
+  #[serde(alias = "dielectric")]
+  Dialetric {
+    refraction_index: f64,
+  },
+  // Phase functions, for objects filled with a medium
+  Isotropic {
+    albedo: RawAlbedo,
+  },
+  #[serde(rename = "henyey_greenstein")]
+  HenyeyGreenstein {
+    albedo: RawAlbedo,
+    #[serde(default)]
+    g: f64,
+  },
+  #[serde(rename = "diffuse_light")]
+  DiffuseLight {
+    colour: [f64; 3],
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+  },
+}
+
+fn default_intensity() -> f64 {
+  1.0
+}
+
+// Read through a plain table for the same reason as RawMaterial
+#[derive(Deserialize)]
+#[serde(try_from = "toml::Table")]
+struct RawObject {
+  kind: RawObjectKind,
+  // Poses to move the object through while the shutter is open, none for a still object
+  keyframes: Vec<RawKeyframe>,
+  // Fills the object with fog or smoke of this density, scattered by its material, in place
+  // of a solid surface
+  density: Option<f64>,
+}
+
+impl TryFrom<toml::Table> for RawObject {
+  type Error = toml::de::Error;
+
+  fn try_from(mut table: toml::Table) -> Result<Self, Self::Error> {
+    Ok(RawObject {
+      keyframes: take(&mut table, "keyframes")?.unwrap_or_default(),
+      density: take(&mut table, "density")?,
+      kind: RawObjectKind::deserialize(toml::Value::Table(table))?,
+    })
+  }
+}
+
+// Removes key from table and deserializes it, if it's there
+fn take<T: DeserializeOwned>(
+  table: &mut toml::Table,
+  key: &str,
+) -> Result<Option<T>, toml::de::Error> {
+  table.remove(key).map(T::deserialize).transpose()
+}
+
+// Any TOML document, checked for NaN and infinite numbers. TOML allows them, but no value in a
+// scene makes sense with one, and they slip past checks like radius < 0.
+struct FiniteNumbers;
+
+impl<'de> Deserialize<'de> for FiniteNumbers {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_any(FiniteNumbers)
+  }
+}
+
+impl<'de> Visitor<'de> for FiniteNumbers {
+  type Value = FiniteNumbers;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("any TOML value")
+  }
+
+  fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+    if v.is_finite() {
+      Ok(FiniteNumbers)
+    } else {
+      Err(E::custom("numbers must be finite"))
+    }
+  }
+
+  fn visit_i64<E: de::Error>(self, _v: i64) -> Result<Self::Value, E> {
+    Ok(FiniteNumbers)
+  }
+
+  fn visit_u64<E: de::Error>(self, _v: u64) -> Result<Self::Value, E> {
+    Ok(FiniteNumbers)
+  }
+
+  fn visit_bool<E: de::Error>(self, _v: bool) -> Result<Self::Value, E> {
+    Ok(FiniteNumbers)
+  }
+
+  fn visit_str<E: de::Error>(self, _v: &str) -> Result<Self::Value, E> {
+    Ok(FiniteNumbers)
+  }
+
+  fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+    while seq.next_element::<FiniteNumbers>()?.is_some() {}
+    Ok(FiniteNumbers)
+  }
+
+  fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    while map.next_entry::<de::IgnoredAny, FiniteNumbers>()?.is_some() {}
+    Ok(FiniteNumbers)
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum RawObjectKind {
+  Sphere {
+    center: [f64; 3],
+    // Where the center has moved to by time 1, for a sphere moving in a straight line
+    #[serde(default)]
+    center1: Option<[f64; 3]>,
+    radius: f64,
+    material: String,
+  },
+  Triangle {
+    vertices: [[f64; 3]; 3],
+    #[serde(default)]
+    normals: Option<[[f64; 3]; 3]>,
+    #[serde(default)]
+    uvs: Option<[[f64; 2]; 3]>,
+    material: String,
+  },
+  // Infinite plane
+  Plane {
+    point: [f64; 3],
+    normal: [f64; 3],
+    material: String,
+  },
+  // Parallelogram with corners corner, corner + u, corner + v and corner + u + v
+  Quad {
+    corner: [f64; 3],
+    u: [f64; 3],
+    v: [f64; 3],
+    material: String,
+  },
+  // Axis-aligned box given by two opposite corners
+  Box {
+    corners: [[f64; 3]; 2],
+    material: String,
+  },
+  // Wavefront OBJ file, using its own MTL materials where it has them and material elsewhere
+  Mesh {
+    file: String,
+    material: String,
+  },
+}
+
+impl RawObjectKind {
+  fn material(&self) -> &str {
+    match self {
+      RawObjectKind::Sphere { material, .. }
+      | RawObjectKind::Triangle { material, .. }
+      | RawObjectKind::Plane { material, .. }
+      | RawObjectKind::Quad { material, .. }
+      | RawObjectKind::Box { material, .. }
+      | RawObjectKind::Mesh { material, .. } => material,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawKeyframe {
+  time: f64,
+  translation: [f64; 3],
+  axis: [f64; 3],
+  // Degrees
+  angle: f64,
+  scale: f64,
+}
+
+impl Default for RawKeyframe {
+  fn default() -> Self {
+    RawKeyframe {
+      time: 0.0,
+      translation: [0.0, 0.0, 0.0],
+      axis: [0.0, 1.0, 0.0],
+      angle: 0.0,
+      scale: 1.0,
+    }
+  }
+}
+
+pub fn load(path: impl AsRef<path::Path>) -> Result<Scene, SceneError> {
+  let path = path.as_ref();
+  let src = fs::read_to_string(path)?;
+  parse_in(&src, path.parent().unwrap_or(path::Path::new("")))
+}
+
+fn parse_in(src: &str, dir: &path::Path) -> Result<Scene, SceneError> {
+  let parse_error = |e: toml::de::Error| SceneError::Parse {
+    line: e.span().map_or(1, |span| line_of(src, span.start)),
+    message: e.message().trim_end().to_string(),
+  };
+  toml::from_str::<FiniteNumbers>(src).map_err(parse_error)?;
+  let raw: RawScene = toml::from_str(src).map_err(parse_error)?;
+
+  let mut camera = match raw.camera {
+    Some(c) => build_camera(src, c)?,
+    None => build_camera(src, Spanned::new(0..0, RawCamera::default()))?,
+  };
+  if let Some(e) = raw.environment {
+    camera.set_environment(build_environment(src, dir, e)?);
+  }
+
+  // Built in file order, so that the first mistake in the file is the one reported
+  let mut textures: HashMap<String, sync::Arc<dyn texture::Texture>> = HashMap::new();
+  for (name, t) in in_file_order(raw.textures) {
+    textures.insert(name, build_texture(src, dir, t)?);
+  }
+
+  let mut materials: HashMap<String, sync::Arc<dyn material::Material>> = HashMap::new();
+  let mut light_materials = HashSet::new();
+  for (name, m) in in_file_order(raw.materials) {
+    if matches!(m.get_ref().kind, RawMaterialKind::DiffuseLight { .. }) {
+      light_materials.insert(name.clone());
+    }
+    materials.insert(name, build_material(src, m, &textures)?);
+  }
+
+  let mut world = hittable_list::HittableList::new_empty();
+  // Objects with a light material that can be sampled directly. Planes are infinite and meshes
+  // don't support it, so those only light the scene when rays happen to hit them.
+  let mut lights = hittable_list::HittableList::new_empty();
+  for o in raw.objects {
+    let line = line_of(src, o.span().start);
+    let lookup = |name: &str| {
+      materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| invalid(line, &format!("unknown material `{name}`")))
+    };
+    let o = o.into_inner();
+    // Moving objects are only ever looked for where they are at time 0, and filled ones scatter
+    // rather than emit, so neither can be sampled
+    let is_light = match &o.kind {
+      RawObjectKind::Sphere { center1, .. } => center1.is_none(),
+      RawObjectKind::Triangle { .. } | RawObjectKind::Quad { .. } | RawObjectKind::Box { .. } => {
+        true
+      }
+      RawObjectKind::Plane { .. } | RawObjectKind::Mesh { .. } => false,
+    } && light_materials.contains(o.kind.material())
+      && o.keyframes.is_empty()
+      && o.density.is_none();
+    // A filled object's material scatters inside it, its boundary is never seen
+    let phase_material = o.kind.material().to_string();
+    let object: sync::Arc<dyn hittable::Hittable> = match o.kind {
+      RawObjectKind::Sphere {
+        center,
+        center1,
+        radius,
+        material,
+      } => {
+        if radius < 0.0 {
+          return Err(invalid(line, "sphere radius must not be negative"));
+        }
+        sync::Arc::new(sphere::Sphere::moving(
+          to_vector(center),
+          to_vector(center1.unwrap_or(center)),
+          radius,
+          lookup(&material)?,
+        ))
+      }
+      RawObjectKind::Triangle {
+        vertices,
+        normals,
+        uvs,
+        material,
+      } => {
+        let [v0, v1, v2] = vertices.map(to_vector);
+        if (v1 - v0).cross(&(v2 - v0)).length_squared() == 0.0 {
+          return Err(invalid(line, "triangle vertices must not be collinear"));
+        }
+        let mut tri = triangle::Triangle::new(v0, v1, v2, lookup(&material)?);
+        if let Some(normals) = normals {
+          tri = tri.with_normals(normals.map(to_vector));
+        }
+        if let Some(uvs) = uvs {
+          tri = tri.with_uvs(uvs.map(|[u, v]| (u, v)));
+        }
+        sync::Arc::new(tri)
+      }
+      RawObjectKind::Plane {
+        point,
+        normal,
+        material,
+      } => {
+        if normal == [0.0; 3] {
+          return Err(invalid(line, "plane normal must not be zero"));
+        }
+        sync::Arc::new(plane::Plane::new(
+          to_vector(point),
+          to_vector(normal),
+          lookup(&material)?,
+        ))
+      }
+      RawObjectKind::Quad {
+        corner,
+        u,
+        v,
+        material,
+      } => {
+        let (u, v) = (to_vector(u), to_vector(v));
+        if u.cross(&v).length_squared() == 0.0 {
+          return Err(invalid(line, "quad u and v must not be parallel"));
+        }
+        sync::Arc::new(quad::Quad::new(to_vector(corner), u, v, lookup(&material)?))
+      }
+      RawObjectKind::Box { corners, material } => {
+        if (0..3).any(|n| corners[0][n] == corners[1][n]) {
+          return Err(invalid(line, "box corners must differ along every axis"));
+        }
+        let [a, b] = corners.map(to_vector);
+        sync::Arc::new(quad::Cuboid::new(a, b, lookup(&material)?))
+      }
+      RawObjectKind::Mesh { file, material } => {
+        let mesh = obj::load(dir.join(file), lookup(&material)?)
+          .map_err(|e| invalid(line, &e.to_string()))?;
+        sync::Arc::new(mesh)
+      }
+    };
+    let object = if o.keyframes.is_empty() {
+      object
+    } else {
+      sync::Arc::new(build_animation(line, object, o.keyframes)?)
+    };
+    // Filled after animating, so the boundary is already where it should be in world space
+    let object: sync::Arc<dyn hittable::Hittable> = match o.density {
+      None => object,
+      Some(density) => {
+        if density <= 0.0 {
+          return Err(invalid(line, "object density must be greater than 0"));
+        }
+        let phase = lookup(&phase_material)?;
+        sync::Arc::new(medium::ConstantMedium::new(object, density, phase))
+      }
+    };
+    if is_light {
+      lights.add(object.clone());
+    }
+    world.add(object);
+  }
+  camera.set_lights(lights);
+
+  Ok(Scene { camera, world })
+}
+
+fn build_camera(src: &str, c: Spanned<RawCamera>) -> Result<camera::Camera, SceneError> {
+  let line = line_of(src, c.span().start);
+  let c = c.into_inner();
+
+  if c.aspect_ratio <= 0.0 {
+    return Err(invalid(line, "camera aspect_ratio must be greater than 0"));
+  }
+  if c.image_width == 0 {
+    return Err(invalid(line, "camera image_width must be greater than 0"));
+  }
+  if c.max_depth == 0 {
+    return Err(invalid(line, "camera max_depth must be greater than 0"));
+  }
+  if c.samples_per_pixel == 0 {
+    return Err(invalid(
+      line,
+      "camera samples_per_pixel must be greater than 0",
+    ));
+  }
+  if c.vfov <= 0.0 || c.vfov >= 180.0 {
+    return Err(invalid(
+      line,
+      "camera vfov must be between 0 and 180 degrees",
+    ));
+  }
+  if c.defocus_angle < 0.0 {
+    return Err(invalid(line, "camera defocus_angle must not be negative"));
+  }
+  if c.focus_dist <= 0.0 {
+    return Err(invalid(line, "camera focus_dist must be greater than 0"));
+  }
+  if c.shutter_close < c.shutter_open {
+    return Err(invalid(
+      line,
+      "camera shutter_close must not be before shutter_open",
+    ));
+  }
+  if c.lookfrom == c.lookat {
+    return Err(invalid(line, "camera lookfrom and lookat must differ"));
+  }
+  let view = to_vector(c.lookfrom) - to_vector(c.lookat);
+  if view.cross(&to_vector(c.vup)).length_squared() == 0.0 {
+    return Err(invalid(
+      line,
+      "camera vup must not be zero or parallel to lookfrom - lookat",
+    ));
+  }
+
+  let mut camera = camera::Camera::new(
+    c.aspect_ratio,
+    c.image_width,
+    c.samples_per_pixel,
+    c.max_depth,
+    to_vector(c.lookfrom),
+    to_vector(c.lookat),
+    to_vector(c.vup),
+    c.vfov,
+    c.defocus_angle,
+    c.focus_dist,
+  );
+  camera.set_roulette_depth(c.roulette_depth);
+  camera.set_shutter(c.shutter_open, c.shutter_close);
+  Ok(camera)
+}
+
+fn build_animation(
+  line: usize,
+  object: sync::Arc<dyn hittable::Hittable>,
+  keyframes: Vec<RawKeyframe>,
+) -> Result<transform::Animated, SceneError> {
+  let mut converted = Vec::with_capacity(keyframes.len());
+  for k in keyframes {
+    if k.scale <= 0.0 {
+      return Err(invalid(line, "keyframe scale must be greater than 0"));
+    }
+    if k.axis == [0.0; 3] {
+      return Err(invalid(line, "keyframe axis must not be zero"));
+    }
+    if converted
+      .iter()
+      .any(|c: &transform::Keyframe| c.time == k.time)
+    {
+      return Err(invalid(line, "keyframe times must differ"));
+    }
+    converted.push(transform::Keyframe {
+      time: k.time,
+      translation: to_vector(k.translation),
+      axis: to_vector(k.axis),
+      angle: k.angle,
+      scale: k.scale,
+    });
+  }
+  Ok(transform::Animated::new(object, converted))
+}
+
+fn build_environment(
+  src: &str,
+  dir: &path::Path,
+  e: Spanned<RawEnvironment>,
+) -> Result<Box<dyn environment::Environment>, SceneError> {
+  let line = line_of(src, e.span().start);
+  match e.into_inner() {
+    RawEnvironment::Sky => Ok(Box::new(environment::Gradient::sky())),
+    RawEnvironment::Solid { colour } => Ok(Box::new(environment::Solid {
+      colour: to_vector(colour),
+    })),
+    RawEnvironment::Gradient { bottom, top, up } => {
+      if up == [0.0; 3] {
+        return Err(invalid(line, "environment up must not be zero"));
+      }
+      Ok(Box::new(environment::Gradient {
+        bottom: to_vector(bottom),
+        top: to_vector(top),
+        up: to_vector(up),
+      }))
+    }
+    RawEnvironment::Map {
+      file,
+      rotation,
+      intensity,
+    } => {
+      if intensity < 0.0 {
+        return Err(invalid(line, "environment intensity must not be negative"));
+      }
+      let path = dir.join(file);
+      let map = environment::Map::load(&path, rotation, intensity)
+        .map_err(|e| invalid(line, &format!("{}: {e}", path.display())))?;
+      Ok(Box::new(map))
+    }
+  }
+}
+
+fn build_texture(
+  src: &str,
+  dir: &path::Path,
+  t: Spanned<RawTexture>,
+) -> Result<sync::Arc<dyn texture::Texture>, SceneError> {
+  let line = line_of(src, t.span().start);
+  match t.into_inner() {
+    RawTexture::Solid { colour } => Ok(sync::Arc::new(texture::Solid {
+      colour: to_vector(colour),
+    })),
+    RawTexture::Checker { scale, even, odd } => {
+      if scale <= 0.0 {
+        return Err(invalid(line, "checker scale must be greater than 0"));
+      }
+      Ok(sync::Arc::new(texture::Checker {
+        scale,
+        even: sync::Arc::new(texture::Solid {
+          colour: to_vector(even),
+        }),
+        odd: sync::Arc::new(texture::Solid {
+          colour: to_vector(odd),
+        }),
+      }))
+    }
+    RawTexture::Image { file, wrap, linear } => {
+      let wrap = match wrap {
+        RawWrap::Repeat => texture::Wrap::Repeat,
+        RawWrap::Clamp => texture::Wrap::Clamp,
+        RawWrap::Mirror => texture::Wrap::Mirror,
+      };
+      let path = dir.join(file);
+      let image = if linear {
+        texture::Image::load_linear(&path, wrap)
+      } else {
+        texture::Image::load(&path, wrap)
+      };
+      let image = image.map_err(|e| invalid(line, &format!("{}: {e}", path.display())))?;
+      Ok(sync::Arc::new(image))
+    }
+    RawTexture::Noise {
+      frequency,
+      low,
+      high,
+      seed,
+    } => {
+      check_frequency(line, frequency)?;
+      Ok(sync::Arc::new(texture::Noise {
+        perlin: perlin::Perlin::new(seed),
+        frequency,
+        low: to_vector(low),
+        high: to_vector(high),
+      }))
+    }
+    RawTexture::Turbulence {
+      frequency,
+      octaves,
+      low,
+      high,
+      seed,
+    } => {
+      check_frequency(line, frequency)?;
+      check_octaves(line, octaves)?;
+      Ok(sync::Arc::new(texture::Turbulence {
+        perlin: perlin::Perlin::new(seed),
+        frequency,
+        octaves,
+        low: to_vector(low),
+        high: to_vector(high),
+      }))
+    }
+    RawTexture::Marble {
+      frequency,
+      octaves,
+      distortion,
+      low,
+      high,
+      seed,
+    } => {
+      check_frequency(line, frequency)?;
+      check_octaves(line, octaves)?;
+      Ok(sync::Arc::new(texture::Marble {
+        perlin: perlin::Perlin::new(seed),
+        frequency,
+        octaves,
+        distortion,
+        low: to_vector(low),
+        high: to_vector(high),
+      }))
+    }
+    RawTexture::Wood {
+      frequency,
+      octaves,
+      distortion,
+      low,
+      high,
+      seed,
+    } => {
+      check_frequency(line, frequency)?;
+      check_octaves(line, octaves)?;
+      Ok(sync::Arc::new(texture::Wood {
+        perlin: perlin::Perlin::new(seed),
+        frequency,
+        octaves,
+        distortion,
+        low: to_vector(low),
+        high: to_vector(high),
+      }))
+    }
+  }
+}
+
+fn check_frequency(line: usize, frequency: f64) -> Result<(), SceneError> {
+  if frequency <= 0.0 {
+    return Err(invalid(line, "noise frequency must be greater than 0"));
+  }
+  Ok(())
+}
+
+// Each octave doubles the frequency and halves the weight, so past this many they add nothing
+// visible and only cost time
+const MAX_OCTAVES: u32 = 16;
+
+fn check_octaves(line: usize, octaves: u32) -> Result<(), SceneError> {
+  if !(1..=MAX_OCTAVES).contains(&octaves) {
+    return Err(invalid(
+      line,
+      &format!("noise octaves must be between 1 and {MAX_OCTAVES}"),
+    ));
+  }
+  Ok(())
+}
+
+fn build_material(
+  src: &str,
+  m: Spanned<RawMaterial>,
+  textures: &HashMap<String, sync::Arc<dyn texture::Texture>>,
+) -> Result<sync::Arc<dyn material::Material>, SceneError> {
+  let line = line_of(src, m.span().start);
+  let lookup = |name: &str| {
+    textures
+      .get(name)
+      .cloned()
+      .ok_or_else(|| invalid(line, &format!("unknown texture `{name}`")))
+  };
+  let texture_of = |albedo: RawAlbedo| -> Result<sync::Arc<dyn texture::Texture>, SceneError> {
+    match albedo {
+      RawAlbedo::Colour(colour) => Ok(sync::Arc::new(texture::Solid {
+        colour: to_vector(colour),
+      })),
+      RawAlbedo::Texture(name) => lookup(&name),
+    }
+  };
+
+  let m = m.into_inner();
+  let base: sync::Arc<dyn material::Material> = match m.kind {
+    RawMaterialKind::Lambertian { albedo } => sync::Arc::new(material::Lambertian {
+      albedo: texture_of(albedo)?,
+    }),
+    RawMaterialKind::Metal { albedo, fuzz } => {
+      if !(0.0..=1.0).contains(&fuzz) {
+        return Err(invalid(line, "metal fuzz must be between 0 and 1"));
+      }
+      sync::Arc::new(material::Metal {
+        albedo: texture_of(albedo)?,
+        fuzz,
+      })
+    }
+    RawMaterialKind::Dialetric { refraction_index } => {
+      if refraction_index <= 0.0 {
+        return Err(invalid(
+          line,
+          "dialetric refraction_index must be greater than 0",
+        ));
+      }
+      sync::Arc::new(material::Dialetric { refraction_index })
+    }
+    RawMaterialKind::Isotropic { albedo } => sync::Arc::new(material::Isotropic {
+      albedo: texture_of(albedo)?,
+    }),
+    RawMaterialKind::HenyeyGreenstein { albedo, g } => {
+      if g <= -1.0 || g >= 1.0 {
+        return Err(invalid(
+          line,
+          "henyey_greenstein g must be between -1 and 1",
+        ));
+      }
+      sync::Arc::new(material::HenyeyGreenstein {
+        albedo: texture_of(albedo)?,
+        g,
+      })
+    }
+    RawMaterialKind::DiffuseLight { colour, intensity } => {
+      if intensity < 0.0 {
+        return Err(invalid(
+          line,
+          "diffuse_light intensity must not be negative",
+        ));
+      }
+      sync::Arc::new(material::DiffuseLight {
+        colour: to_vector(colour),
+        intensity,
+      })
+    }
+  };
+
+  let map = match (m.normal_map, m.bump_map) {
+    (None, None) => return Ok(base),
+    (Some(_), Some(_)) => {
+      return Err(invalid(
+        line,
+        "material can't have both a normal_map and a bump_map",
+      ))
+    }
+    (Some(name), None) => normal_map::NormalMap::Tangent(lookup(&name)?),
+    (None, Some(name)) => normal_map::NormalMap::Bump {
+      height: lookup(&name)?,
+      strength: m.bump_strength,
+    },
+  };
+  Ok(sync::Arc::new(material::NormalMapped { base, map }))
+}
+
+fn in_file_order<T>(tables: HashMap<String, Spanned<T>>) -> Vec<(String, Spanned<T>)> {
+  let mut tables: Vec<_> = tables.into_iter().collect();
+  tables.sort_by_key(|(_, t)| t.span().start);
+  tables
+}
+
+fn invalid(line: usize, message: &str) -> SceneError {
+  SceneError::Parse {
+    line,
+    message: message.to_string(),
+  }
+}
+
+fn line_of(src: &str, offset: usize) -> usize {
+  src[..offset.min(src.len())].matches('\n').count() + 1
+}
+
+fn to_vector(v: [f64; 3]) -> vector::Vector {
+  vector::Vector::new(v[0], v[1], v[2])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Line and message of the error parsing src, which must fail
+  fn error(src: &str) -> (usize, String) {
+    match parse_in(src, path::Path::new("")) {
+      Err(SceneError::Parse { line, message }) => (line, message),
+      Err(e) => panic!("unexpected error {e}"),
+      Ok(_) => panic!("scene parsed"),
+    }
+  }
+
+  #[test]
+  fn loads_example_scenes() {
+    for file in ["scenes/three_spheres.toml", "scenes/cornell_box.toml"] {
+      if let Err(e) = load(file) {
+        panic!("{file}: {e}");
+      }
+    }
+  }
+
+  #[test]
+  fn rejects_unknown_material_keys() {
+    let (line, message) = error(
+      "[materials.steel]\n\
+       type = \"metal\"\n\
+       albedo = [0.5, 0.5, 0.5]\n\
+       fuz = 0.3\n",
+    );
+    assert_eq!(line, 1);
+    assert!(message.contains("fuz"), "{message}");
+  }
+
+  #[test]
+  fn rejects_unknown_object_keys() {
+    let (line, message) = error(
+      "[materials.white]\n\
+       type = \"lambertian\"\n\
+       albedo = [1.0, 1.0, 1.0]\n\
+       \n\
+       [[objects]]\n\
+       type = \"sphere\"\n\
+       center = [0.0, 0.0, 0.0]\n\
+       raduis = 1.0\n\
+       material = \"white\"\n",
+    );
+    assert_eq!(line, 5);
+    assert!(message.contains("raduis"), "{message}");
+  }
+
+  #[test]
+  fn rejects_non_finite_numbers() {
+    let (line, message) = error("[camera]\nvfov = 40.0\nfocus_dist = nan\n");
+    assert_eq!(line, 3);
+    assert!(message.contains("finite"), "{message}");
+    let (line, _) = error("[camera]\nlookat = [0.0, inf, 0.0]\n");
+    assert_eq!(line, 2);
+  }
+
+  #[test]
+  fn rejects_zero_max_depth() {
+    let (line, message) = error("\n[camera]\nmax_depth = 0\n");
+    assert_eq!(line, 2);
+    assert!(message.contains("max_depth"), "{message}");
+  }
+
+  #[test]
+  fn reports_the_first_mistake_in_the_file() {
+    let mut src = String::new();
+    for i in 0..8 {
+      src += &format!("[materials.m{i}]\ntype = \"metal\"\nalbedo = [1.0, 1.0, 1.0]\nfuzz = 2.0\n");
+    }
+    for _ in 0..10 {
+      assert_eq!(error(&src).0, 1);
+    }
+  }
+
+  #[test]
+  fn rejects_vup_along_the_view_direction() {
+    let (line, message) = error("[camera]\nlookfrom = [0.3, 3.3, 0.0]\nlookat = [0.3, 0.3, 0.0]\n");
+    assert_eq!(line, 1);
+    assert!(message.contains("vup"), "{message}");
+  }
+
+  // Line and message of the error for a white object given by the keys in object
+  fn object_error(object: &str) -> (usize, String) {
+    error(&format!(
+      "[materials.white]\ntype = \"lambertian\"\nalbedo = [1.0, 1.0, 1.0]\n\n[[objects]]\n\
+       material = \"white\"\n{object}"
+    ))
+  }
+
+  #[test]
+  fn rejects_collinear_triangles() {
+    let (line, message) = object_error(
+      "type = \"triangle\"\nvertices = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [2.0, 2.0, 2.0]]\n",
+    );
+    assert_eq!(line, 5);
+    assert!(message.contains("collinear"), "{message}");
+  }
+
+  #[test]
+  fn rejects_flat_boxes() {
+    for corners in [
+      "[[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]]",
+      "[[0.0, 0.0, 0.0], [1.0, 0.0, 1.0]]",
+    ] {
+      let (line, message) = object_error(&format!("type = \"box\"\ncorners = {corners}\n"));
+      assert_eq!(line, 5);
+      assert!(message.contains("box corners"), "{message}");
+    }
+  }
+
+  #[test]
+  fn rejects_zero_keyframe_axis() {
+    let (line, message) = object_error(
+      "type = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+       keyframes = [{ time = 0.0, axis = [0.0, 0.0, 0.0], angle = 30.0 }]\n",
+    );
+    assert_eq!(line, 5);
+    assert!(message.contains("axis"), "{message}");
+  }
+
+  #[test]
+  fn rejects_too_many_octaves() {
+    let (line, message) =
+      error("\n[textures.clouds]\ntype = \"turbulence\"\noctaves = 1000000000\n");
+    assert_eq!(line, 2);
+    assert!(message.contains("octaves"), "{message}");
+  }
+}
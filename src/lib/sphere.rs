@@ -1,39 +1,62 @@
 use crate::lib::ray::Ray;
 use crate::lib::vector::Vector;
-use crate::lib::{hittable, interval, material};
-use std::rc;
+use crate::lib::{aabb, hittable, interval, material, utility};
+use std::sync;
 
 pub struct Sphere {
+  // Where the center is at time 0
   pub center: Vector,
   pub radius: f64,
-  mat: rc::Rc<dyn material::Material>,
+  // Distance the center moves per unit of time
+  velocity: Vector,
+  mat: sync::Arc<dyn material::Material>,
+  bbox: aabb::Aabb,
 }
 
 impl Sphere {
-  pub fn new(center: Vector, radius: f64, mat: rc::Rc<dyn material::Material>) -> Sphere {
+  pub fn new(center: Vector, radius: f64, mat: sync::Arc<dyn material::Material>) -> Sphere {
+    Sphere::moving(center, center, radius, mat)
+  }
+
+  // Sphere moving in a straight line from center0 at time 0 to center1 at time 1, resting at
+  // either end outside that interval so that its bounding box holds whatever the shutter.
+  // Light sampling always looks for it at center0, so a moving sphere shouldn't be one of the
+  // camera's lights.
+  pub fn moving(
+    center0: Vector,
+    center1: Vector,
+    radius: f64,
+    mat: sync::Arc<dyn material::Material>,
+  ) -> Sphere {
+    let radius = radius.max(0.0);
+    let rvec = Vector::new(radius, radius, radius);
     Sphere {
-      center,
-      radius: radius.max(0.0),
+      center: center0,
+      radius,
+      velocity: center1 - center0,
       mat,
+      bbox: aabb::Aabb::enclosing(
+        &aabb::Aabb::from_points(center0 - rvec, center0 + rvec),
+        &aabb::Aabb::from_points(center1 - rvec, center1 + rvec),
+      ),
     }
   }
+
+  fn center_at(&self, time: f64) -> Vector {
+    self.center + (self.velocity * time.clamp(0.0, 1.0))
+  }
 }
 
 impl material::Material for Sphere {
-  fn scatter(
-    &self,
-    _r_in: &super::ray::Ray,
-    _rec: &hittable::HitRecord,
-    _attenuation: &mut Vector,
-    _scattered: &mut Ray,
-  ) -> bool {
-    return false;
+  fn sample(&self, _r_in: &Ray, _rec: &hittable::HitRecord) -> Option<material::Sample> {
+    None
   }
 }
 
 impl hittable::Hittable for Sphere {
   fn hit(&self, r: &Ray, ray_t: interval::Interval, hit: &mut hittable::HitRecord) -> bool {
-    let oc = self.center - r.orig;
+    let center = self.center_at(r.time);
+    let oc = center - r.orig;
     let a = r.dir.length_squared();
     let h = r.dir.dot(&oc);
     let c = oc.length_squared() - (self.radius * self.radius);
@@ -54,10 +77,122 @@ impl hittable::Hittable for Sphere {
 
     hit.t = root;
     hit.point = r.at(hit.t);
-    let outward_normal: Vector = (hit.point - self.center) / self.radius;
+    let outward_normal: Vector = (hit.point - center) / self.radius;
     hit.set_face_normal(r, outward_normal);
+    (hit.u, hit.v) = Sphere::uv(&outward_normal);
+    (hit.dpdu, hit.dpdv) = self.tangents(&outward_normal);
     hit.mat = self.mat.clone();
 
-    return true;
+    true
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+
+  // Directions are picked uniformly within the cone the sphere fills as seen from origin. From
+  // inside the sphere it fills every direction, and random picks from all of them.
+  fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+      return 1.0 / (4.0 * utility::PI);
+    };
+    let mut hit = hittable::HitRecord::new_empty();
+    let ray = Ray::new(*origin, *direction);
+    if !self.hit(
+      &ray,
+      interval::Interval::new(0.001, utility::INFINITY),
+      &mut hit,
+    ) {
+      return 0.0;
+    }
+    1.0 / (2.0 * utility::PI * (1.0 - cos_theta_max))
+  }
+
+  fn random(&self, origin: &Vector) -> Vector {
+    let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+      return Vector::random_unit_vector();
+    };
+    let w = (self.center - *origin).unit_vector();
+    let (v, u) = w.onb();
+
+    let z = 1.0 + utility::random_df() * (cos_theta_max - 1.0);
+    let phi = 2.0 * utility::PI * utility::random_df();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    (u * (phi.cos() * r)) + (v * (phi.sin() * r)) + (w * z)
+  }
+}
+
+impl Sphere {
+  // Texture coordinates of the point at unit offset p from the center. u goes once around the
+  // y axis starting from -x, through +z, and v from the bottom pole to the top one.
+  fn uv(p: &Vector) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + utility::PI;
+    (phi / (2.0 * utility::PI), theta / utility::PI)
+  }
+
+  // Derivatives of the point at unit offset p from the center with respect to u and v. At the
+  // poles, where u is undefined, dpdu vanishes and dpdv is left at zero.
+  fn tangents(&self, p: &Vector) -> (Vector, Vector) {
+    let ring = (p.x * p.x + p.z * p.z).sqrt();
+    let dpdu = Vector::new(p.z, 0.0, -p.x) * (2.0 * utility::PI * self.radius);
+    if ring == 0.0 {
+      return (dpdu, Vector::new(0.0, 0.0, 0.0));
+    }
+    let dpdv =
+      Vector::new(-p.x * p.y / ring, ring, -p.y * p.z / ring) * (utility::PI * self.radius);
+    (dpdu, dpdv)
+  }
+
+  // Cosine of the half-angle of the cone the sphere fills as seen from origin, or None when
+  // origin is inside it
+  fn cos_theta_max(&self, origin: &Vector) -> Option<f64> {
+    let distance_squared = (self.center - *origin).length_squared();
+    let radius_squared = self.radius * self.radius;
+    (distance_squared > radius_squared).then(|| (1.0 - radius_squared / distance_squared).sqrt())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lib::hittable::Hittable;
+
+  fn unit_sphere() -> Sphere {
+    Sphere::new(
+      Vector::new(0.0, 0.0, 0.0),
+      1.0,
+      sync::Arc::new(material::Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+    )
+  }
+
+  #[test]
+  fn pdf_value_matches_random_from_inside() {
+    let sphere = unit_sphere();
+    let origin = Vector::new(0.2, -0.3, 0.1);
+    utility::with_seed(1, || {
+      for _ in 0..100 {
+        let direction = sphere.random(&origin);
+        assert_eq!(
+          sphere.pdf_value(&origin, &direction),
+          1.0 / (4.0 * utility::PI)
+        );
+      }
+    });
+  }
+
+  #[test]
+  fn pdf_value_covers_the_cone_from_outside() {
+    let sphere = unit_sphere();
+    let origin = Vector::new(0.0, 0.0, 2.0);
+    // The sphere fills a cone of half-angle 30 degrees, a solid angle of 2 pi (1 - cos 30)
+    let expected = 1.0 / (2.0 * utility::PI * (1.0 - 0.75f64.sqrt()));
+    utility::with_seed(2, || {
+      for _ in 0..100 {
+        let direction = sphere.random(&origin);
+        assert!((sphere.pdf_value(&origin, &direction) - expected).abs() < 1e-9);
+      }
+    });
+    assert_eq!(sphere.pdf_value(&origin, &Vector::new(0.0, 0.0, 1.0)), 0.0);
   }
 }
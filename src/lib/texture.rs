@@ -0,0 +1,240 @@
+use crate::lib::vector::Vector;
+use crate::lib::{colour, perlin, utility};
+use std::{path, sync};
+
+// Colour that varies over a surface, looked up from the texture coordinates and position of a hit
+pub trait Texture: Send + Sync {
+  fn value(&self, u: f64, v: f64, point: &Vector) -> Vector;
+}
+
+// The same colour everywhere
+pub struct Solid {
+  pub colour: Vector,
+}
+
+impl Texture for Solid {
+  fn value(&self, _u: f64, _v: f64, _point: &Vector) -> Vector {
+    self.colour
+  }
+}
+
+// Space filled with alternating cubes of even and odd, each scale wide. It depends on the hit
+// point rather than u and v, so it works on surfaces without texture coordinates.
+pub struct Checker {
+  pub scale: f64,
+  pub even: sync::Arc<dyn Texture>,
+  pub odd: sync::Arc<dyn Texture>,
+}
+
+impl Texture for Checker {
+  fn value(&self, u: f64, v: f64, point: &Vector) -> Vector {
+    let cell = |x: f64| (x / self.scale).floor() as i64;
+    if (cell(point.x) + cell(point.y) + cell(point.z)).rem_euclid(2) == 0 {
+      self.even.value(u, v, point)
+    } else {
+      self.odd.value(u, v, point)
+    }
+  }
+}
+
+// Smooth random blotches blending between low and high, about 1 / frequency across
+pub struct Noise {
+  pub perlin: perlin::Perlin,
+  pub frequency: f64,
+  pub low: Vector,
+  pub high: Vector,
+}
+
+impl Texture for Noise {
+  fn value(&self, _u: f64, _v: f64, point: &Vector) -> Vector {
+    let t = 0.5 * (1.0 + self.perlin.noise(&(*point * self.frequency)));
+    blend(self.low, self.high, t)
+  }
+}
+
+// Rough, cloudy variation from octaves layers of noise, blending between low and high
+pub struct Turbulence {
+  pub perlin: perlin::Perlin,
+  pub frequency: f64,
+  pub octaves: u32,
+  pub low: Vector,
+  pub high: Vector,
+}
+
+impl Texture for Turbulence {
+  fn value(&self, _u: f64, _v: f64, point: &Vector) -> Vector {
+    let t = self
+      .perlin
+      .turbulence(&(*point * self.frequency), self.octaves)
+      .min(1.0);
+    blend(self.low, self.high, t)
+  }
+}
+
+// Veins of low through high, frequency of them per unit along z, pushed about by turbulence.
+// distortion is how many veins' width the turbulence can shift them by.
+pub struct Marble {
+  pub perlin: perlin::Perlin,
+  pub frequency: f64,
+  pub octaves: u32,
+  pub distortion: f64,
+  pub low: Vector,
+  pub high: Vector,
+}
+
+impl Texture for Marble {
+  fn value(&self, _u: f64, _v: f64, point: &Vector) -> Vector {
+    let p = *point * self.frequency;
+    let turbulence = self.perlin.turbulence(&p, self.octaves);
+    blend(
+      self.low,
+      self.high,
+      bands(p.z + self.distortion * turbulence),
+    )
+  }
+}
+
+// Growth rings around the y axis, frequency of them per unit, wobbled by turbulence in the same
+// way as Marble's veins
+pub struct Wood {
+  pub perlin: perlin::Perlin,
+  pub frequency: f64,
+  pub octaves: u32,
+  pub distortion: f64,
+  pub low: Vector,
+  pub high: Vector,
+}
+
+impl Texture for Wood {
+  fn value(&self, _u: f64, _v: f64, point: &Vector) -> Vector {
+    let p = *point * self.frequency;
+    let radius = (p.x * p.x + p.z * p.z).sqrt();
+    let turbulence = self.perlin.turbulence(&p, self.octaves);
+    blend(
+      self.low,
+      self.high,
+      bands(radius + self.distortion * turbulence),
+    )
+  }
+}
+
+// Rises smoothly from 0 to 1 and back once for every unit of x
+fn bands(x: f64) -> f64 {
+  0.5 * (1.0 + (2.0 * utility::PI * x).sin())
+}
+
+fn blend(low: Vector, high: Vector, t: f64) -> Vector {
+  low * (1.0 - t) + high * t
+}
+
+// How texture coordinates outside 0 to 1 are mapped back onto an image
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Wrap {
+  // Tiles the image
+  Repeat,
+  // Stretches the edge pixels outwards
+  Clamp,
+  // Tiles the image, flipping every other copy so that edges meet seamlessly
+  Mirror,
+}
+
+impl Wrap {
+  fn apply(&self, i: i64, n: usize) -> usize {
+    let n = n as i64;
+    let i = match *self {
+      Wrap::Repeat => i.rem_euclid(n),
+      Wrap::Clamp => i.clamp(0, n - 1),
+      Wrap::Mirror => {
+        let m = i.rem_euclid(2 * n);
+        if m < n {
+          m
+        } else {
+          2 * n - 1 - m
+        }
+      }
+    };
+    i as usize
+  }
+}
+
+// Image stretched over the surface's texture coordinates, with u running left to right and v
+// bottom to top. Pixels are blended bilinearly.
+pub struct Image {
+  width: usize,
+  height: usize,
+  // Linear colours, row by row from the top
+  pixels: Vec<Vector>,
+  wrap: Wrap,
+}
+
+impl Image {
+  // Loads an image such as a PNG or JPEG, whose pixels are taken to be sRGB encoded
+  pub fn load(path: impl AsRef<path::Path>, wrap: Wrap) -> Result<Image, image::ImageError> {
+    Image::load_with(path, wrap, colour::srgb_to_linear)
+  }
+
+  // Loads an image whose pixels are data rather than colours, such as a normal map, unchanged
+  pub fn load_linear(path: impl AsRef<path::Path>, wrap: Wrap) -> Result<Image, image::ImageError> {
+    Image::load_with(path, wrap, |c| c)
+  }
+
+  fn load_with(
+    path: impl AsRef<path::Path>,
+    wrap: Wrap,
+    decode: fn(f64) -> f64,
+  ) -> Result<Image, image::ImageError> {
+    let image = image::open(path)?.into_rgb32f();
+    let pixels = image
+      .pixels()
+      .map(|p| {
+        Vector::new(
+          decode(p[0].into()),
+          decode(p[1].into()),
+          decode(p[2].into()),
+        )
+      })
+      .collect();
+    Ok(Image::new(
+      image.width() as usize,
+      image.height() as usize,
+      pixels,
+      wrap,
+    ))
+  }
+
+  // Panics unless there are width * height pixels
+  pub fn new(width: usize, height: usize, pixels: Vec<Vector>, wrap: Wrap) -> Image {
+    assert_eq!(pixels.len(), width * height);
+    Image {
+      width,
+      height,
+      pixels,
+      wrap,
+    }
+  }
+
+  fn pixel(&self, x: i64, y: i64) -> Vector {
+    let x = self.wrap.apply(x, self.width);
+    let y = self.wrap.apply(y, self.height);
+    self.pixels[y * self.width + x]
+  }
+}
+
+impl Texture for Image {
+  fn value(&self, u: f64, v: f64, _point: &Vector) -> Vector {
+    if self.pixels.is_empty() {
+      return Vector::new(0.0, 0.0, 0.0);
+    }
+
+    // Position in pixels, measured so that pixel centres fall on whole numbers
+    let x = u * self.width as f64 - 0.5;
+    let y = (1.0 - v) * self.height as f64 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let top = self.pixel(x0, y0) * (1.0 - fx) + self.pixel(x0 + 1, y0) * fx;
+    let bottom = self.pixel(x0, y0 + 1) * (1.0 - fx) + self.pixel(x0 + 1, y0 + 1) * fx;
+    top * (1.0 - fy) + bottom * fy
+  }
+}
@@ -0,0 +1,229 @@
+use crate::lib::interval::Interval;
+use crate::lib::ray::Ray;
+use crate::lib::vector::Vector;
+use crate::lib::{aabb, hittable, utility};
+use std::sync;
+
+// Pose of an animated object at one moment. The object is scaled, then turned angle degrees
+// anticlockwise about axis (looking back along it), both about its own origin, then moved by
+// translation.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Keyframe {
+  pub time: f64,
+  pub translation: Vector,
+  pub axis: Vector,
+  pub angle: f64,
+  pub scale: f64,
+}
+
+// An object moving through a series of keyframes. Between two keyframes the translation and
+// scale are interpolated linearly and the rotation along the shortest arc. Before the first
+// keyframe and after the last, the object holds still.
+//
+// Animated objects can't be sampled as lights.
+pub struct Animated {
+  object: sync::Arc<dyn hittable::Hittable>,
+  // In time order, with rotations as quaternions
+  poses: Vec<(f64, Pose)>,
+  bbox: aabb::Aabb,
+}
+
+impl Animated {
+  // Panics if there are no keyframes
+  pub fn new(object: sync::Arc<dyn hittable::Hittable>, mut keyframes: Vec<Keyframe>) -> Animated {
+    assert!(
+      !keyframes.is_empty(),
+      "animation needs at least one keyframe"
+    );
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    let poses: Vec<(f64, Pose)> = keyframes
+      .iter()
+      .map(|k| {
+        let pose = Pose {
+          translation: k.translation,
+          rotation: Quaternion::from_axis_angle(&k.axis, utility::deg_to_rad(k.angle)),
+          scale: k.scale,
+        };
+        (k.time, pose)
+      })
+      .collect();
+
+    let bbox = Animated::bounds(&object.bounding_box(), &poses);
+    Animated {
+      object,
+      poses,
+      bbox,
+    }
+  }
+
+  fn pose_at(&self, time: f64) -> Pose {
+    let next = self.poses.partition_point(|(t, _)| *t <= time);
+    if next == 0 {
+      return self.poses[0].1;
+    }
+    if next == self.poses.len() {
+      return self.poses[next - 1].1;
+    }
+
+    let (t0, a) = &self.poses[next - 1];
+    let (t1, b) = &self.poses[next];
+    let f = (time - t0) / (t1 - t0);
+    Pose {
+      translation: a.translation + ((b.translation - a.translation) * f),
+      rotation: Quaternion::slerp(&a.rotation, &b.rotation, f),
+      scale: a.scale + (b.scale - a.scale) * f,
+    }
+  }
+
+  // Box around every pose. While the rotation or scale is changing, any point of the object
+  // stays within its furthest corner's distance from the origin, scaled; otherwise the object
+  // just slides between the boxes at either end.
+  fn bounds(object: &aabb::Aabb, poses: &[(f64, Pose)]) -> aabb::Aabb {
+    if object.is_unbounded() {
+      return aabb::UNIVERSE;
+    }
+
+    let corners: Vec<Vector> = (0..8)
+      .map(|i| {
+        let pick = |axis: &Interval, bit: usize| {
+          if i & bit == 0 {
+            axis.min
+          } else {
+            axis.max
+          }
+        };
+        Vector::new(pick(&object.x, 1), pick(&object.y, 2), pick(&object.z, 4))
+      })
+      .collect();
+    let reach = corners.iter().map(|c| c.length()).fold(0.0, f64::max);
+
+    let posed = |pose: &Pose| {
+      corners
+        .iter()
+        .map(|c| pose.apply(c))
+        .map(|p| aabb::Aabb::from_points(p, p))
+        .reduce(|a, b| aabb::Aabb::enclosing(&a, &b))
+        .unwrap()
+    };
+    let mut bbox = posed(&poses[0].1);
+    for pair in poses.windows(2) {
+      let (a, b) = (&pair[0].1, &pair[1].1);
+      bbox = aabb::Aabb::enclosing(&bbox, &posed(b));
+      if a.rotation != b.rotation || a.scale != b.scale {
+        let radius = reach * a.scale.abs().max(b.scale.abs());
+        let r = Vector::new(radius, radius, radius);
+        for t in [a.translation, b.translation] {
+          bbox = aabb::Aabb::enclosing(&bbox, &aabb::Aabb::from_points(t - r, t + r));
+        }
+      }
+    }
+    bbox
+  }
+}
+
+impl hittable::Hittable for Animated {
+  fn hit(&self, r: &Ray, ray_t: Interval, hit: &mut hittable::HitRecord) -> bool {
+    // Move the ray into the object's own space, where distances along it are unchanged
+    let pose = self.pose_at(r.time);
+    let inverse = pose.rotation.conjugate();
+    let local = Ray::with_time(
+      inverse.rotate(&(r.orig - pose.translation)) / pose.scale,
+      inverse.rotate(&r.dir) / pose.scale,
+      r.time,
+    );
+    if !self.object.hit(&local, ray_t, hit) {
+      return false;
+    }
+
+    hit.point = pose.apply(&hit.point);
+    hit.normal = pose.rotation.rotate(&hit.normal);
+    hit.dpdu = pose.rotation.rotate(&hit.dpdu) * pose.scale;
+    hit.dpdv = pose.rotation.rotate(&hit.dpdv) * pose.scale;
+    true
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Pose {
+  translation: Vector,
+  rotation: Quaternion,
+  scale: f64,
+}
+
+impl Pose {
+  // Where the point p of the object ends up
+  fn apply(&self, p: &Vector) -> Vector {
+    self.rotation.rotate(&(*p * self.scale)) + self.translation
+  }
+}
+
+// Unit quaternion w + v, representing a rotation
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Quaternion {
+  w: f64,
+  v: Vector,
+}
+
+impl Quaternion {
+  // Turns angle radians about axis, or nothing if axis is zero
+  fn from_axis_angle(axis: &Vector, angle: f64) -> Quaternion {
+    if axis.length_squared() == 0.0 {
+      return Quaternion {
+        w: 1.0,
+        v: Vector::new(0.0, 0.0, 0.0),
+      };
+    }
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    Quaternion {
+      w: cos,
+      v: axis.unit_vector() * sin,
+    }
+  }
+
+  fn conjugate(&self) -> Quaternion {
+    Quaternion {
+      w: self.w,
+      v: self.v * -1.0,
+    }
+  }
+
+  fn rotate(&self, p: &Vector) -> Vector {
+    let t = self.v.cross(p) * 2.0;
+    *p + (t * self.w) + self.v.cross(&t)
+  }
+
+  // Rotation a fraction f of the way from a to b, turning at a constant rate
+  fn slerp(a: &Quaternion, b: &Quaternion, f: f64) -> Quaternion {
+    // q and -q are the same rotation; pick whichever is closer to a for the shorter arc
+    let mut cos = a.w * b.w + a.v.dot(&b.v);
+    let b = if cos < 0.0 {
+      cos = -cos;
+      Quaternion {
+        w: -b.w,
+        v: b.v * -1.0,
+      }
+    } else {
+      *b
+    };
+
+    let (wa, wb) = if cos > 0.9995 {
+      // Nearly the same rotation, where slerp's division is unstable and a lerp is as good
+      (1.0 - f, f)
+    } else {
+      let theta = cos.acos();
+      let sin = theta.sin();
+      (((1.0 - f) * theta).sin() / sin, (f * theta).sin() / sin)
+    };
+    let w = a.w * wa + b.w * wb;
+    let v = (a.v * wa) + (b.v * wb);
+    let length = (w * w + v.length_squared()).sqrt();
+    Quaternion {
+      w: w / length,
+      v: v / length,
+    }
+  }
+}
@@ -0,0 +1,272 @@
+use crate::lib::ray::Ray;
+use crate::lib::vector::Vector;
+use crate::lib::{aabb, hittable, interval, material, utility};
+use std::sync;
+
+pub struct Triangle {
+  v0: Vector,
+  v1: Vector,
+  v2: Vector,
+  // Per-vertex shading normals for smooth shading, in vertex order
+  normals: Option<[Vector; 3]>,
+  // Per-vertex texture coordinates, in vertex order
+  uvs: Option<[(f64, f64); 3]>,
+  mat: sync::Arc<dyn material::Material>,
+  bbox: aabb::Aabb,
+}
+
+impl Triangle {
+  // Flat shaded triangle. Vertices wound counter-clockwise when seen from the front.
+  pub fn new(
+    v0: Vector,
+    v1: Vector,
+    v2: Vector,
+    mat: sync::Arc<dyn material::Material>,
+  ) -> Triangle {
+    let bbox = aabb::Aabb::enclosing(
+      &aabb::Aabb::from_points(v0, v1),
+      &aabb::Aabb::from_points(v2, v2),
+    );
+    Triangle {
+      v0,
+      v1,
+      v2,
+      normals: None,
+      uvs: None,
+      mat,
+      bbox,
+    }
+  }
+
+  // Interpolates these vertex normals across the face instead of using the flat normal
+  pub fn with_normals(mut self, normals: [Vector; 3]) -> Triangle {
+    self.normals = Some(normals.map(|n| n.unit_vector()));
+    self
+  }
+
+  // Interpolates these vertex uvs into the hit record instead of the barycentric coordinates
+  pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Triangle {
+    self.uvs = Some(uvs);
+    self
+  }
+}
+
+// Where a ray crosses a triangle
+pub struct Intersection {
+  pub t: f64,
+  // Barycentric weights of the three vertices at the hit point
+  pub weights: [f64; 3],
+  // Unit normal of the triangle's plane, facing the side the vertices wind counter-clockwise
+  pub normal: Vector,
+  // v1 - v0 and v2 - v0
+  pub edges: [Vector; 2],
+}
+
+// Möller–Trumbore: solves origin + t*dir = (1-b1-b2)*v0 + b1*v1 + b2*v2 for t, b1 and b2
+pub fn intersect(
+  [v0, v1, v2]: [Vector; 3],
+  r: &Ray,
+  ray_t: interval::Interval,
+) -> Option<Intersection> {
+  let edge1 = v1 - v0;
+  let edge2 = v2 - v0;
+
+  let p = r.dir.cross(&edge2);
+  let det = edge1.dot(&p);
+  // Ray parallel to the triangle's plane
+  if det.abs() < 1e-12 {
+    return None;
+  }
+  let inv_det = 1.0 / det;
+
+  let s = r.orig - v0;
+  let b1 = s.dot(&p) * inv_det;
+  if !(0.0..=1.0).contains(&b1) {
+    return None;
+  }
+
+  let q = s.cross(&edge1);
+  let b2 = r.dir.dot(&q) * inv_det;
+  if b2 < 0.0 || b1 + b2 > 1.0 {
+    return None;
+  }
+
+  let t = edge2.dot(&q) * inv_det;
+  if !ray_t.surrounds(t) {
+    return None;
+  }
+
+  Some(Intersection {
+    t,
+    weights: [1.0 - b1 - b2, b1, b2],
+    normal: edge1.cross(&edge2).unit_vector(),
+    edges: [edge1, edge2],
+  })
+}
+
+impl Intersection {
+  // Fills everything in the hit record except the material. Vertex normals and uvs are
+  // interpolated when given; otherwise the face is flat and uv is (b1, b2).
+  pub fn fill(
+    &self,
+    r: &Ray,
+    normals: Option<[Vector; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    hit: &mut hittable::HitRecord,
+  ) {
+    let [b0, b1, b2] = self.weights;
+    hit.t = self.t;
+    hit.point = r.at(self.t);
+    hit.set_face_normal(r, self.normal);
+    if let Some([n0, n1, n2]) = normals {
+      // Shade with the interpolated normal, on whichever side set_face_normal chose
+      let shading = ((n0 * b0) + (n1 * b1) + (n2 * b2)).unit_vector();
+      hit.normal = if hit.front_face {
+        shading
+      } else {
+        shading * -1.0
+      };
+    }
+    (hit.u, hit.v) = match uvs {
+      Some([uv0, uv1, uv2]) => (
+        (uv0.0 * b0) + (uv1.0 * b1) + (uv2.0 * b2),
+        (uv0.1 * b0) + (uv1.1 * b1) + (uv2.1 * b2),
+      ),
+      None => (b1, b2),
+    };
+    (hit.dpdu, hit.dpdv) = self.tangents(uvs);
+  }
+
+  // The edges are linear in u and v, so dp/du and dp/dv come from solving
+  //   edge1 = du1 * dpdu + dv1 * dpdv
+  //   edge2 = du2 * dpdu + dv2 * dpdv
+  // Without uvs, or with degenerate ones, u and v are b1 and b2, which run along the edges.
+  fn tangents(&self, uvs: Option<[(f64, f64); 3]>) -> (Vector, Vector) {
+    let [edge1, edge2] = self.edges;
+    if let Some([uv0, uv1, uv2]) = uvs {
+      let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+      let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+      let det = du1 * dv2 - du2 * dv1;
+      if det.abs() > 1e-12 {
+        let dpdu = ((edge1 * dv2) - (edge2 * dv1)) / det;
+        let dpdv = ((edge2 * du1) - (edge1 * du2)) / det;
+        return (dpdu, dpdv);
+      }
+    }
+    (edge1, edge2)
+  }
+}
+
+impl hittable::Hittable for Triangle {
+  fn hit(&self, r: &Ray, ray_t: interval::Interval, hit: &mut hittable::HitRecord) -> bool {
+    match intersect([self.v0, self.v1, self.v2], r, ray_t) {
+      Some(intersection) => {
+        intersection.fill(r, self.normals, self.uvs, hit);
+        hit.mat = self.mat.clone();
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn bounding_box(&self) -> aabb::Aabb {
+    self.bbox
+  }
+
+  fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    let ray = Ray::new(*origin, *direction);
+    let vertices = [self.v0, self.v1, self.v2];
+    let Some(hit) = intersect(
+      vertices,
+      &ray,
+      interval::Interval::new(0.001, utility::INFINITY),
+    ) else {
+      return 0.0;
+    };
+
+    let area = (self.v1 - self.v0).cross(&(self.v2 - self.v0)).length() / 2.0;
+    hittable::area_light_pdf(direction, hit.t, &hit.normal, area)
+  }
+
+  fn random(&self, origin: &Vector) -> Vector {
+    // Folding the unit square onto the triangle keeps the points uniform
+    let (mut b1, mut b2) = (utility::random_df(), utility::random_df());
+    if b1 + b2 > 1.0 {
+      (b1, b2) = (1.0 - b1, 1.0 - b2);
+    }
+    let p = self.v0 + ((self.v1 - self.v0) * b1) + ((self.v2 - self.v0) * b2);
+    p - *origin
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lib::hittable::Hittable;
+
+  // Right triangle in the z = 0 plane, facing +z
+  fn triangle() -> Triangle {
+    Triangle::new(
+      Vector::new(0.0, 0.0, 0.0),
+      Vector::new(1.0, 0.0, 0.0),
+      Vector::new(0.0, 1.0, 0.0),
+      sync::Arc::new(material::Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+    )
+  }
+
+  fn hit(tri: &Triangle, r: &Ray, ray_t: interval::Interval) -> Option<hittable::HitRecord> {
+    let mut rec = hittable::HitRecord::new_empty();
+    tri.hit(r, ray_t, &mut rec).then_some(rec)
+  }
+
+  fn down_at(x: f64, y: f64) -> Ray {
+    Ray::new(Vector::new(x, y, 1.0), Vector::new(0.0, 0.0, -1.0))
+  }
+
+  const AHEAD: interval::Interval = interval::Interval::new(0.001, utility::INFINITY);
+
+  #[test]
+  fn hits_exactly_on_edges() {
+    let tri = triangle();
+    for (x, y) in [(0.5, 0.0), (0.0, 0.5), (0.5, 0.5), (0.0, 0.0)] {
+      let rec = hit(&tri, &down_at(x, y), AHEAD).unwrap_or_else(|| panic!("missed ({x}, {y})"));
+      assert_eq!(rec.t, 1.0);
+      assert_eq!(rec.point, Vector::new(x, y, 0.0));
+    }
+    assert!(hit(&tri, &down_at(0.5, -1e-9), AHEAD).is_none());
+    assert!(hit(&tri, &down_at(0.5, 0.5 + 1e-9), AHEAD).is_none());
+  }
+
+  #[test]
+  fn flips_the_normal_on_the_back_face() {
+    let tri = triangle();
+    let front = hit(&tri, &down_at(0.25, 0.25), AHEAD).unwrap();
+    assert!(front.front_face);
+    assert_eq!(front.normal, Vector::new(0.0, 0.0, 1.0));
+
+    let up = Ray::new(Vector::new(0.25, 0.25, -1.0), Vector::new(0.0, 0.0, 1.0));
+    let back = hit(&tri, &up, AHEAD).unwrap();
+    assert!(!back.front_face);
+    assert_eq!(back.normal, Vector::new(0.0, 0.0, -1.0));
+
+    // Interpolated vertex normals are flipped the same way
+    let smooth = triangle().with_normals([Vector::new(0.0, 0.0, 1.0); 3]);
+    let back = hit(&smooth, &up, AHEAD).unwrap();
+    assert!(!back.front_face);
+    assert_eq!(back.normal, Vector::new(0.0, 0.0, -1.0));
+  }
+
+  #[test]
+  fn respects_the_interval() {
+    let tri = triangle();
+    let r = down_at(0.25, 0.25);
+    assert!(hit(&tri, &r, interval::Interval::new(0.001, 0.5)).is_none());
+    assert!(hit(&tri, &r, interval::Interval::new(1.5, 2.0)).is_none());
+    assert_eq!(
+      hit(&tri, &r, interval::Interval::new(0.001, 1.5))
+        .unwrap()
+        .t,
+      1.0
+    );
+  }
+}
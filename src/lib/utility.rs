@@ -1,18 +1,63 @@
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::cell::RefCell;
 
 pub const PI: f64 = std::f64::consts::PI;
-pub const INFINITY: f64 = std::f64::INFINITY;
+pub const INFINITY: f64 = f64::INFINITY;
+
+thread_local! {
+  // Each thread draws from its own generator, seeded from the OS unless reseeded with seed()
+  static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_os_rng());
+}
 
 pub fn deg_to_rad(d: f64) -> f64 {
   d.to_radians()
 }
 
+// Reseeds the calling thread's generator so the numbers it draws next are reproducible
+pub fn seed(seed: u64) {
+  RNG.with(|r| *r.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// Runs f with the calling thread's generator seeded with seed, then puts the generator back as it
+// was, so that f draws the same numbers every time without disturbing anything else
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+  // Puts the generator back when dropped, which happens even if f panics
+  struct Restore(Option<StdRng>);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      if let Some(previous) = self.0.take() {
+        RNG.with(|r| *r.borrow_mut() = previous);
+      }
+    }
+  }
+
+  let _restore = Restore(Some(RNG.with(|r| r.replace(StdRng::seed_from_u64(seed)))));
+  f()
+}
+
 pub fn random_df() -> f64 {
-  let mut r = rand::rng();
-  r.random_range(0.0..1.0)
+  RNG.with(|r| r.borrow_mut().random_range(0.0..1.0))
 }
 
 pub fn random(min: f64, max: f64) -> f64 {
-  let mut r = rand::rng();
-  r.random_range(min..max)
+  RNG.with(|r| r.borrow_mut().random_range(min..max))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::panic;
+
+  #[test]
+  fn with_seed_restores_the_generator_after_a_panic() {
+    seed(5);
+    let expected = random_df();
+
+    seed(5);
+    let result = panic::catch_unwind(|| with_seed(9, || panic!("inside with_seed")));
+    assert!(result.is_err());
+    assert_eq!(random_df(), expected);
+  }
 }
@@ -13,10 +13,20 @@ impl Vector {
     Vector { x, y, z }
   }
 
+  #[allow(dead_code)]
   pub fn get_vector(&self) -> (f64, f64, f64) {
     (self.x, self.y, self.z)
   }
 
+  // Component along axis n, where 0 is x, 1 is y and 2 is z
+  pub fn axis(&self, n: usize) -> f64 {
+    match n {
+      0 => self.x,
+      1 => self.y,
+      _ => self.z,
+    }
+  }
+
   pub fn length_squared(&self) -> f64 {
     (self.x * self.x) + (self.y * self.y) + (self.z * self.z)
   }
@@ -35,7 +45,7 @@ impl Vector {
 
   pub fn random_unit_vector() -> Vector {
     loop {
-      let p = Vector::random_df();
+      let p = Vector::random(-1.0, 1.0);
       let lensq = p.length_squared();
       if 1e-160 < lensq && lensq <= 1.0 {
         return p / lensq.sqrt();
@@ -43,12 +53,13 @@ impl Vector {
     }
   }
 
+  #[allow(dead_code)]
   pub fn random_on_hemisphere(&self) -> Vector {
     let on_unit_sphere = Vector::random_unit_vector();
     if on_unit_sphere.dot(self) > 0.0 {
-      return on_unit_sphere;
+      on_unit_sphere
     } else {
-      return on_unit_sphere * -1.0;
+      on_unit_sphere * -1.0
     }
   }
 
@@ -70,7 +81,7 @@ impl Vector {
 
   pub fn near_zero(&self) -> bool {
     let s = 1e-8;
-    (self.x < s) && (self.y < s) && (self.z < s)
+    (self.x.abs() < s) && (self.y.abs() < s) && (self.z.abs() < s)
   }
 
   pub fn reflect(&self, n: Vector) -> Vector {
@@ -81,7 +92,7 @@ impl Vector {
     let cos_theta = (*self * -1.0).dot(&n).min(1.0);
     let r_out_perp = (*self + (n * cos_theta)) * etai_over_etat;
     let r_out_parralel = n * -((1.0 - r_out_perp.length_squared()).abs().sqrt());
-    return r_out_perp + r_out_parralel;
+    r_out_perp + r_out_parralel
   }
 
   pub fn cross(&self, v: &Vector) -> Vector {
@@ -92,6 +103,18 @@ impl Vector {
     )
   }
 
+  // Two unit vectors perpendicular to this unit vector and to each other, with
+  // self.cross(&first) == second
+  pub fn onb(&self) -> (Vector, Vector) {
+    let helper = if self.x.abs() > 0.9 {
+      Vector::new(0.0, 1.0, 0.0)
+    } else {
+      Vector::new(1.0, 0.0, 0.0)
+    };
+    let first = self.cross(&helper).unit_vector();
+    (first, self.cross(&first))
+  }
+
   pub fn random_in_unit_disk() -> Vector {
     loop {
       let p = Vector::new(utility::random(-1.0, 1.0), utility::random(-1.0, 1.0), 0.0);
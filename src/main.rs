@@ -1,104 +1,106 @@
-mod lib;
-use lib::{camera, hittable_list, material, sphere, utility, vector};
-use std::rc::Rc;
+#![allow(special_module_name)]
 
-fn main() {
-  let mut world = hittable_list::HittableList::new_empty();
+mod cli;
+mod lib;
+mod scenes;
 
-  let ground_material = Rc::new(material::Lambertian {
-    albedo: vector::Vector::new(0.5, 0.5, 0.5),
-  });
-  world.add(Rc::new(sphere::Sphere::new(
-    vector::Vector::new(0.0, -1000.0, 0.0),
-    1000.0,
-    ground_material.clone(),
-  )));
+use clap::Parser;
+use image::ImageFormat;
+use lib::{bvh, environment, framebuffer, gltf, progress, scene, utility};
+use std::{path, process};
 
-  for a in -11..11 {
-    for b in -11..11 {
-      let choose_mat = utility::random_df();
-      let center = vector::Vector::new(
-        a as f64 + 0.9 * utility::random_df(),
-        0.2,
-        b as f64 + 0.9 * utility::random_df(),
-      );
+fn main() {
+  let options = cli::Options::parse();
 
-      if (center - vector::Vector::new(4.0, 0.2, 0.0)).length() > 0.9 {
-        let sphere_material: Rc<dyn material::Material> = if choose_mat < 0.8 {
-          // Diffuse
-          Rc::new(material::Lambertian {
-            albedo: vector::Vector::random_df() * vector::Vector::random_df(),
-          })
-        } else if choose_mat < 0.95 {
-          // Metal
-          Rc::new(material::Metal {
-            albedo: vector::Vector::random(0.5, 1.0),
-            fuzz: utility::random(0.0, 0.5),
-          })
-        } else {
-          // Glass
-          Rc::new(material::Dialetric {
-            refraction_index: 1.5,
-          })
-        };
-        world.add(Rc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
-      }
+  match framebuffer::check_extension(&options.output) {
+    Ok(ImageFormat::OpenExr) => {}
+    Ok(_) if options.alpha => {
+      eprintln!("--alpha is only supported for .exr output");
+      process::exit(1);
+    }
+    Ok(_) => {}
+    Err(e) => {
+      eprintln!("{}: {e}", options.output.display());
+      process::exit(1);
     }
   }
 
-  let material1 = Rc::new(material::Dialetric {
-    refraction_index: 1.5,
-  });
-  world.add(Rc::new(sphere::Sphere::new(
-    vector::Vector::new(0.0, 1.0, 0.0),
-    1.0,
-    material1.clone(),
-  )));
-
-  let material2 = Rc::new(material::Lambertian {
-    albedo: vector::Vector::new(0.4, 0.2, 0.1),
-  });
-  world.add(Rc::new(sphere::Sphere::new(
-    vector::Vector::new(-4.0, 1.0, 0.0),
-    1.0,
-    material2.clone(),
-  )));
-
-  let material3 = Rc::new(material::Metal {
-    albedo: vector::Vector::new(0.7, 0.6, 0.5),
-    fuzz: 0.0,
-  });
-  world.add(Rc::new(sphere::Sphere::new(
-    vector::Vector::new(4.0, 1.0, 0.0),
-    1.0,
-    material3.clone(),
-  )));
-
-  let aspect_ratio = 16.0 / 9.0;
-  let image_width = 1200;
-  let samples_per_pixel = 500;
-  let max_depth = 50;
+  if let Some(seed) = options.seed {
+    utility::seed(seed);
+  }
 
-  let lookfrom = vector::Vector::new(13.0, 2.0, 3.0);
-  let lookat = vector::Vector::new(0.0, 0.0, 0.0);
-  let vup = vector::Vector::new(0.0, 1.0, 0.0);
-  let vfov = 20.0;
+  let mut scene = match scenes::builtin(&options.scene) {
+    Some(scene) => scene,
+    None if is_gltf(&options.scene) => {
+      let import = gltf::load(&options.scene).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+      });
+      for warning in &import.warnings {
+        eprintln!("{}: warning: {warning}", options.scene);
+      }
+      import.scene
+    }
+    None => scene::load(&options.scene).unwrap_or_else(|e| {
+      eprintln!("{}: {e}", options.scene);
+      process::exit(1);
+    }),
+  };
 
-  let defocus_angle = 0.6;
-  let focus_dist = 10.0;
+  let camera = &mut scene.camera;
+  if let Some(width) = options.width {
+    camera.set_image_width(width as usize);
+  }
+  if let Some(aspect_ratio) = options.aspect_ratio {
+    camera.set_aspect_ratio(aspect_ratio);
+  }
+  if let Some(samples) = options.samples {
+    camera.set_samples_per_pixel(samples);
+  }
+  if let Some(max_depth) = options.max_depth {
+    camera.set_max_depth(max_depth);
+  }
+  if let Some(roulette_depth) = options.roulette_depth {
+    camera.set_roulette_depth(roulette_depth);
+  }
+  if let Some(threads) = options.threads {
+    camera.set_threads(threads as usize);
+  }
+  camera.set_seed(options.seed);
+  if options.quiet {
+    camera.set_progress(Box::new(progress::Silent));
+  }
+  if let Some(path) = &options.environment {
+    let map = environment::Map::load(
+      path,
+      options.environment_rotation,
+      options.environment_intensity,
+    )
+    .unwrap_or_else(|e| {
+      eprintln!("{}: {e}", path.display());
+      process::exit(1);
+    });
+    camera.set_environment(Box::new(map));
+  }
 
-  let cam = camera::Camera::new(
-    aspect_ratio,
-    image_width,
-    samples_per_pixel,
-    max_depth,
-    lookfrom,
-    lookat,
-    vup,
-    vfov,
-    defocus_angle,
-    focus_dist,
-  );
+  let world = bvh::BvhNode::new(scene.world);
+  let image = scene.camera.render(&world);
+  let saved = if options.alpha {
+    image.save_exr(&options.output, true)
+  } else {
+    image.save(&options.output, &options.display_transform())
+  };
+  if let Err(e) = saved {
+    eprintln!("{}: {e}", options.output.display());
+    process::exit(1);
+  }
+}
 
-  cam.render(&world);
+fn is_gltf(scene: &str) -> bool {
+  let ext = path::Path::new(scene)
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or("")
+    .to_lowercase();
+  ext == "gltf" || ext == "glb"
 }
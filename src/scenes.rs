@@ -0,0 +1,112 @@
+use crate::lib::{camera, hittable_list, material, plane, scene, sphere, utility, vector};
+use std::sync::Arc;
+
+// Looks up a scene compiled into the binary by name
+pub fn builtin(name: &str) -> Option<scene::Scene> {
+  match name {
+    "random-spheres" => Some(random_spheres()),
+    _ => None,
+  }
+}
+
+// The final scene from Ray Tracing in One Weekend: a field of small random spheres
+fn random_spheres() -> scene::Scene {
+  let mut world = hittable_list::HittableList::new_empty();
+
+  let ground_material = Arc::new(material::Lambertian::new(vector::Vector::new(
+    0.5, 0.5, 0.5,
+  )));
+  world.add(Arc::new(plane::Plane::new(
+    vector::Vector::new(0.0, 0.0, 0.0),
+    vector::Vector::new(0.0, 1.0, 0.0),
+    ground_material.clone(),
+  )));
+
+  for a in -11..11 {
+    for b in -11..11 {
+      let choose_mat = utility::random_df();
+      let center = vector::Vector::new(
+        a as f64 + 0.9 * utility::random_df(),
+        0.2,
+        b as f64 + 0.9 * utility::random_df(),
+      );
+
+      if (center - vector::Vector::new(4.0, 0.2, 0.0)).length() > 0.9 {
+        let sphere_material: Arc<dyn material::Material> = if choose_mat < 0.8 {
+          // Diffuse
+          Arc::new(material::Lambertian::new(
+            vector::Vector::random_df() * vector::Vector::random_df(),
+          ))
+        } else if choose_mat < 0.95 {
+          // Metal
+          Arc::new(material::Metal::new(
+            vector::Vector::random(0.5, 1.0),
+            utility::random(0.0, 0.5),
+          ))
+        } else {
+          // Glass
+          Arc::new(material::Dialetric {
+            refraction_index: 1.5,
+          })
+        };
+        world.add(Arc::new(sphere::Sphere::new(center, 0.2, sphere_material)));
+      }
+    }
+  }
+
+  let material1 = Arc::new(material::Dialetric {
+    refraction_index: 1.5,
+  });
+  world.add(Arc::new(sphere::Sphere::new(
+    vector::Vector::new(0.0, 1.0, 0.0),
+    1.0,
+    material1.clone(),
+  )));
+
+  let material2 = Arc::new(material::Lambertian::new(vector::Vector::new(
+    0.4, 0.2, 0.1,
+  )));
+  world.add(Arc::new(sphere::Sphere::new(
+    vector::Vector::new(-4.0, 1.0, 0.0),
+    1.0,
+    material2.clone(),
+  )));
+
+  let material3 = Arc::new(material::Metal::new(
+    vector::Vector::new(0.7, 0.6, 0.5),
+    0.0,
+  ));
+  world.add(Arc::new(sphere::Sphere::new(
+    vector::Vector::new(4.0, 1.0, 0.0),
+    1.0,
+    material3.clone(),
+  )));
+
+  let aspect_ratio = 16.0 / 9.0;
+  let image_width = 1200;
+  let samples_per_pixel = 500;
+  let max_depth = 50;
+
+  let lookfrom = vector::Vector::new(13.0, 2.0, 3.0);
+  let lookat = vector::Vector::new(0.0, 0.0, 0.0);
+  let vup = vector::Vector::new(0.0, 1.0, 0.0);
+  let vfov = 20.0;
+
+  let defocus_angle = 0.6;
+  let focus_dist = 10.0;
+
+  let cam = camera::Camera::new(
+    aspect_ratio,
+    image_width,
+    samples_per_pixel,
+    max_depth,
+    lookfrom,
+    lookat,
+    vup,
+    vfov,
+    defocus_angle,
+    focus_dist,
+  );
+
+  scene::Scene { camera: cam, world }
+}